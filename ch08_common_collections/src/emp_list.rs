@@ -0,0 +1,583 @@
+// The employee list project from the end of the chapter.  It started out as a
+// single function in main.rs, but once it could save its work it grew enough
+// to be given a file of its own, and then to be part of a library so it can
+// be tested and used from other programs.
+//
+// Directory is the org chart itself, and Command is a line of input parsed
+// into what it asks for.  A Session is what runs Commands against a
+// Directory, keeping the history for Undo and saving it all to a file.
+use std::env;
+use std::fs;
+// BufRead is used to allow the for loop (.lines())
+use std::io::{self, BufRead, IsTerminal};
+
+mod command;
+mod csv;
+mod directory;
+mod history;
+mod json;
+mod repl;
+mod storage;
+
+pub use command::Command;
+pub use directory::{Date, Directory, Employee, Field};
+pub use history::History;
+use history::Operation;
+
+// Where the org chart is kept between runs, unless EMP_LIST_FILE says
+// otherwise
+const DEFAULT_FILE: &str = "emp_list.txt";
+
+pub fn emp_list() {
+    // "Add Sally to Engineering";
+    // "Add Joe to Engineering";
+    // "Add Bob to Engineering";
+    // "Add Chris to Analytics";
+    // "Add Sue to Analytics";
+    // "Add Amir to Sales";
+
+    let file = env::var("EMP_LIST_FILE").unwrap_or_else(|_| DEFAULT_FILE.to_string());
+    let (mut session, problem) = Session::open(file);
+    if let Some(problem) = problem {
+        println!("{}", problem);
+        println!(
+            "Starting with an empty org chart, which won't be saved over {} unless you use 'Save'",
+            session.file
+        );
+    }
+
+    println!("Type 'Add <name> to <department>' to add an employee,");
+    println!("'Help' to see everything else, or 'Quit' to save and quit");
+
+    // Someone typing gets a line editor, with Tab completion and the
+    // commands from last time.  Commands piped in don't need any of that.
+    if io::stdin().is_terminal() {
+        println!("Tab finishes off commands, departments and names");
+        if let Err(e) = repl::run(&mut session) {
+            println!("Error: Unable to read user input: {}", e);
+        }
+    } else {
+        read_lines(&mut session);
+    }
+
+    // Reached on Quit, and also when the input runs out, so Ctrl-D or piping
+    // in commands doesn't lose them either
+    if let Err(e) = session.save_on_exit() {
+        println!("{}", e);
+    }
+}
+
+fn read_lines(session: &mut Session) {
+    for line in io::stdin().lock().lines() {
+        let input = line.expect("Error: Unable to read user input");
+        if input.trim().is_empty() {
+            continue;
+        }
+        // Anything that goes wrong is the user's to fix, so we say what it
+        // was and wait for the next command
+        match session.run(&input) {
+            Ok(Some(output)) => print!("{}", output),
+            Ok(None) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+const BATCH_USAGE: &str = "Usage: common_collections --batch [file] [--keep-going] [--json]";
+
+// Batch mode, for scripts: 'common_collections --batch commands.txt' runs
+// the commands in the file (or stdin, without a file or with '-'), without
+// the greeting.  Errors go to stderr with the line they were on, and the
+// first one stops everything, without saving, so a script that goes wrong
+// leaves the org chart as it was.  With --keep-going the rest of the
+// commands still run.  Either way, any error makes the exit code 1.
+//
+// Returns the exit code, for main to exit with.
+pub fn batch(args: &[String]) -> i32 {
+    let mut input = None;
+    let mut keep_going = false;
+    let mut json = false;
+    let mut batch = false;
+    for arg in args {
+        match arg.as_str() {
+            "--batch" => batch = true,
+            "--keep-going" => keep_going = true,
+            "--json" => json = true,
+            _ if arg.starts_with("--") || input.is_some() => {
+                eprintln!("{}", BATCH_USAGE);
+                return 2;
+            }
+            _ => input = Some(arg.clone()),
+        }
+    }
+    if !batch {
+        eprintln!("{}", BATCH_USAGE);
+        return 2;
+    }
+
+    let reader: Box<dyn BufRead> = match input.as_deref() {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => match fs::File::open(path) {
+            Ok(file) => Box::new(io::BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Couldn't read {}: {}", path, e);
+                return 1;
+            }
+        },
+    };
+
+    let file = env::var("EMP_LIST_FILE").unwrap_or_else(|_| DEFAULT_FILE.to_string());
+    let (mut session, problem) = Session::open(file);
+    // Carrying on with an empty org chart would overwrite the one we
+    // couldn't read, which a script shouldn't do without anyone noticing
+    if let Some(problem) = problem {
+        eprintln!("{}", problem);
+        return 1;
+    }
+    session.json = json;
+
+    let mut failed = false;
+    for (index, line) in reader.lines().enumerate() {
+        let input = match line {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("Couldn't read the commands: {}", e);
+                return 1;
+            }
+        };
+        // '#' starts a comment, so scripts can explain themselves
+        if input.trim().is_empty() || input.trim_start().starts_with('#') {
+            continue;
+        }
+        // With --json, only the JSON goes to stdout, so another program can
+        // read it without picking out messages like 'Added Sally ...'
+        let listing = Command::parse_line(&input).is_ok_and(|c| c.lists_employees());
+        match session.run(&input) {
+            Ok(Some(output)) if json && !listing => eprint!("{}", output),
+            Ok(Some(output)) => print!("{}", output),
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("line {}: {}", index + 1, e);
+                failed = true;
+                if !keep_going {
+                    return 1;
+                }
+            }
+        }
+    }
+
+    if let Err(e) = session.save() {
+        eprintln!("{}", e);
+        return 1;
+    }
+    if failed {
+        1
+    } else {
+        0
+    }
+}
+
+// Everything a run of emp_list works with, whether someone is typing the
+// commands or a script is
+pub struct Session {
+    pub directory: Directory,
+    pub history: History,
+    // Where the org chart is saved to
+    pub file: String,
+    // Whether List, All and Search give JSON instead of text
+    pub json: bool,
+    // Whether the org chart is saved on the way out.  It isn't after a file
+    // we couldn't load, so the empty one we started with doesn't replace it,
+    // until a Save or Load says which file we're working with.
+    pub autosave: bool,
+}
+
+impl Session {
+    // Picks up where we left off last time.  Not having a file yet is fine,
+    // but one we can't read is returned as well, since it's worth mentioning
+    pub fn open(file: String) -> (Session, Option<String>) {
+        let (loaded, problem) = match storage::load(&file) {
+            Ok(loaded) => (loaded, None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                ((Directory::new(), History::default()), None)
+            }
+            Err(e) => (
+                (Directory::new(), History::default()),
+                Some(format!("Couldn't load {}: {}", file, e)),
+            ),
+        };
+        let (directory, history) = loaded;
+        let session = Session {
+            directory,
+            history,
+            file,
+            json: false,
+            autosave: problem.is_none(),
+        };
+        (session, problem)
+    }
+
+    // Runs one line of input, returning what it has to say, or None if it
+    // was Quit.  Commands that change the directory are recorded so they can
+    // be undone.
+    pub fn run(&mut self, input: &str) -> Result<Option<String>, String> {
+        let command = Command::parse_line(input)?;
+        if command == Command::Quit {
+            return Ok(None);
+        }
+        // A copy from before the command, to see what it changed
+        let before = if command.changes_directory() {
+            Some(self.directory.clone())
+        } else {
+            None
+        };
+        let output = self.execute(command)?;
+        if let Some(before) = before {
+            self.history
+                .record(Operation::between(input.trim(), &before, &self.directory));
+        }
+        Ok(Some(output))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save(&self.file, &self.directory, &self.history)
+            .map_err(|e| format!("Couldn't save to {}: {}", self.file, e))
+    }
+
+    // Saves on Quit or when the input runs out, unless autosave is off
+    pub fn save_on_exit(&self) -> Result<(), String> {
+        if self.autosave {
+            self.save()
+        } else {
+            Ok(())
+        }
+    }
+
+    // Carries out a command that's already been parsed, returning what it
+    // has to say for whoever is running it to print.  Unlike run, this
+    // doesn't add it to the history, and Quit does nothing.
+    pub fn execute(&mut self, command: Command) -> Result<String, String> {
+        let Session {
+            directory,
+            history,
+            file,
+            json,
+            autosave,
+        } = self;
+        let output = match command {
+            Command::Add { name, department } => {
+                let id = directory.add(&name, &department);
+                format!("Added {} to {} as #{}\n", name, department, id)
+            }
+            Command::Remove { name, department } => {
+                let id = directory.resolve(&name)?;
+                let employee = directory.get(id).unwrap();
+                if let Some(department) = department {
+                    if employee.department != department {
+                        return Err(format!("{} doesn't work in {}", name, department));
+                    }
+                }
+                let employee = directory.remove(id).unwrap();
+                format!(
+                    "Removed {} (#{}) from {}\n",
+                    employee.name, employee.id, employee.department
+                )
+            }
+            Command::Move { name, from, to } => {
+                let id = directory.resolve(&name)?;
+                let employee = directory.get_mut(id).unwrap();
+                if let Some(from) = from {
+                    if employee.department != from {
+                        return Err(format!("{} doesn't work in {}", name, from));
+                    }
+                }
+                if employee.department == to {
+                    return Err(format!("{} is already in {}", name, to));
+                }
+                let from = std::mem::replace(&mut employee.department, to.clone());
+                format!("Moved {} from {} to {}\n", name, from, to)
+            }
+            Command::Rename {
+                department,
+                new_name,
+            } => {
+                directory.rename_department(&department, &new_name)?;
+                format!("Renamed {} to {}\n", department, new_name)
+            }
+            Command::Find { name } => {
+                let found = directory.named(&name);
+                if found.is_empty() {
+                    return Err(format!("No one called {} works here", name));
+                }
+                found
+                    .iter()
+                    .map(|employee| {
+                        format!(
+                            "{}: {}\n",
+                            employee.department,
+                            directory.describe(employee)
+                        )
+                    })
+                    .collect()
+            }
+            Command::Show { name } => {
+                let employee = directory.get(directory.resolve(&name)?).unwrap();
+                let start_date = employee
+                    .start_date
+                    .map_or_else(|| "-".to_string(), |date| date.to_string());
+                let manager = match employee.manager.and_then(|id| directory.get(id)) {
+                    Some(manager) => format!("{} (#{})", manager.name, manager.id),
+                    None => "-".to_string(),
+                };
+                format!(
+                    "Number:     #{}\n\
+                     Name:       {}\n\
+                     Department: {}\n\
+                     Title:      {}\n\
+                     Started:    {}\n\
+                     Manager:    {}\n",
+                    employee.id,
+                    employee.name,
+                    employee.department,
+                    employee.title.as_deref().unwrap_or("-"),
+                    start_date,
+                    manager
+                )
+            }
+            Command::Set { field, name, value } => {
+                let id = directory.resolve(&name)?;
+                match field {
+                    Field::Title => directory.get_mut(id).unwrap().title = Some(value),
+                    Field::Start => {
+                        directory.get_mut(id).unwrap().start_date = Some(Date::parse(&value)?)
+                    }
+                    Field::Manager => {
+                        let manager = directory.resolve(&value)?;
+                        directory.set_manager(id, Some(manager))?;
+                    }
+                }
+                format!("{}\n", directory.describe(directory.get(id).unwrap()))
+            }
+            Command::Clear { field, name } => {
+                let id = directory.resolve(&name)?;
+                let employee = directory.get_mut(id).unwrap();
+                match field {
+                    Field::Title => employee.title = None,
+                    Field::Start => employee.start_date = None,
+                    Field::Manager => employee.manager = None,
+                }
+                format!("{}\n", directory.describe(directory.get(id).unwrap()))
+            }
+            Command::List { department } => {
+                let employees = directory.list(&department);
+                if employees.is_empty() {
+                    return Err(no_one_works_in(directory, &department));
+                }
+                if *json {
+                    format!("{}\n", json::employees(&employees))
+                } else {
+                    employees
+                        .iter()
+                        .map(|employee| {
+                            format!("{}: {}\n", department, directory.describe(employee))
+                        })
+                        .collect()
+                }
+            }
+            Command::All => {
+                let everyone = directory.all();
+                if *json {
+                    format!("{}\n", json::employees(&everyone))
+                } else {
+                    everyone
+                        .iter()
+                        .map(|employee| {
+                            format!(
+                                "{}: {}\n",
+                                employee.department,
+                                directory.describe(employee)
+                            )
+                        })
+                        .collect()
+                }
+            }
+            Command::Search { prefix } => {
+                let found = directory.search(&prefix);
+                if found.is_empty() {
+                    return Err(format!("No one's name starts with {}", prefix));
+                }
+                if *json {
+                    format!("{}\n", json::employees(&found))
+                } else {
+                    found
+                        .iter()
+                        .map(|employee| {
+                            format!(
+                                "{}: {}\n",
+                                employee.department,
+                                directory.describe(employee)
+                            )
+                        })
+                        .collect()
+                }
+            }
+            Command::Count => {
+                let counts = directory.counts();
+                if counts.is_empty() {
+                    return Err("No one works here yet".to_string());
+                }
+                // Wide enough for the longest department name
+                let width = counts.iter().map(|(d, _)| d.chars().count()).max().unwrap();
+                let mut output = String::new();
+                for (department, count) in &counts {
+                    output += &format!("{:width$}  {}\n", department, count, width = width);
+                }
+                output += &format!(
+                    "{:width$}  {}\n",
+                    "Everyone",
+                    directory.employees().count(),
+                    width = width
+                );
+
+                // There can be a tie for either
+                let most = counts.iter().map(|(_, count)| *count).max().unwrap();
+                let least = counts.iter().map(|(_, count)| *count).min().unwrap();
+                let with = |n: usize| -> String {
+                    let departments: Vec<&str> = counts
+                        .iter()
+                        .filter(|(_, count)| *count == n)
+                        .map(|(department, _)| *department)
+                        .collect();
+                    format!("{} ({})", departments.join(", "), n)
+                };
+                output += &format!("Largest: {}\n", with(most));
+                output += &format!("Smallest: {}\n", with(least));
+                output
+            }
+            Command::Tree { department } => {
+                if let Some(department) = &department {
+                    if directory.list(department).is_empty() {
+                        return Err(no_one_works_in(directory, department));
+                    }
+                }
+                directory.tree(department.as_deref())
+            }
+            Command::Chain { name } => {
+                let employee = directory.get(directory.resolve(&name)?).unwrap();
+                let mut chain = directory.chain_of_command(employee.id);
+                if chain.is_empty() {
+                    return Err(format!("{} doesn't report to anyone", employee.name));
+                }
+                // From the top down, like the piece of the tree that leads to
+                // them
+                chain.reverse();
+                chain.push(employee);
+                chain
+                    .into_iter()
+                    .enumerate()
+                    .map(|(depth, employee)| {
+                        format!("{}{}\n", "  ".repeat(depth), directory.summary(employee))
+                    })
+                    .collect()
+            }
+            // Saving somewhere new also makes that the file we save to on the way
+            // out
+            Command::Save { file: new_file } => {
+                let new_file = new_file.unwrap_or_else(|| file.clone());
+                storage::save(&new_file, directory, history)
+                    .map_err(|e| format!("Couldn't save to {}: {}", new_file, e))?;
+                let output = format!("Saved to {}\n", new_file);
+                *file = new_file;
+                *autosave = true;
+                output
+            }
+            // Anything not saved is replaced by what's in the file, history and
+            // all
+            Command::Load { file: new_file } => {
+                let new_file = new_file.unwrap_or_else(|| file.clone());
+                (*directory, *history) = storage::load(&new_file)
+                    .map_err(|e| format!("Couldn't load {}: {}", new_file, e))?;
+                let output = format!("Loaded {}\n", new_file);
+                *file = new_file;
+                *autosave = true;
+                output
+            }
+            Command::Import { file, dry_run } => {
+                let text = fs::read_to_string(&file)
+                    .map_err(|e| format!("Couldn't read {}: {}", file, e))?;
+                // A dry run imports into a copy, which is then thrown away
+                let mut copy;
+                let target = if dry_run {
+                    copy = directory.clone();
+                    &mut copy
+                } else {
+                    &mut *directory
+                };
+                let imported = csv::import(target, &text).map_err(|errors| {
+                    format!("Nothing was imported from {}:\n{}", file, errors.join("\n"))
+                })?;
+                let mut output = String::new();
+                for header in &imported.ignored {
+                    output += &format!("Ignored the {} column\n", header);
+                }
+                output += &format!(
+                    "{} {} from {}\n",
+                    if dry_run { "Would change" } else { "Changed" },
+                    employees(imported.changes.len()),
+                    file
+                );
+                for change in &imported.changes {
+                    output += &format!("  {}\n", csv::describe(target, change));
+                }
+                output
+            }
+            Command::Export { file } => {
+                fs::write(&file, csv::export(directory))
+                    .map_err(|e| format!("Couldn't write {}: {}", file, e))?;
+                format!(
+                    "Exported {} to {}\n",
+                    employees(directory.employees().count()),
+                    file
+                )
+            }
+            Command::Undo => format!("Undid {}\n", history.undo(directory)?.description),
+            Command::Redo => format!("Redid {}\n", history.redo(directory)?.description),
+            Command::History => {
+                if history.operations.is_empty() {
+                    return Ok("Nothing has been changed yet\n".to_string());
+                }
+                let done = history.done();
+                history
+                    .operations
+                    .iter()
+                    .enumerate()
+                    .map(|(index, operation)| {
+                        let undone = if index < done { "" } else { "  (undone)" };
+                        format!("{:>3}. {}{}\n", index + 1, operation.description, undone)
+                    })
+                    .collect()
+            }
+            Command::Help => format!("{}\n", command::HELP),
+            Command::Quit => String::new(),
+        };
+        Ok(output)
+    }
+}
+
+// "1 employee" or "2 employees"
+fn employees(count: usize) -> String {
+    if count == 1 {
+        "1 employee".to_string()
+    } else {
+        format!("{} employees", count)
+    }
+}
+
+// The error for a department no one works in, with a guess at the one that
+// was meant if there's one close enough
+fn no_one_works_in(directory: &Directory, department: &str) -> String {
+    match directory.closest_department(department) {
+        Some(closest) => format!("No one works in {}.  Did you mean {}?", department, closest),
+        None => format!("No one works in {}", department),
+    }
+}
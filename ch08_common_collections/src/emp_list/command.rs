@@ -0,0 +1,482 @@
+// Turning a line of input into a Command.  The line is first split into
+// words, with anything in double quotes kept together as one word, and then
+// the words are matched against the shapes each command can take.
+//
+// Names and departments can be several words long, so 'Add Mary Ann to Human
+// Resources' works as you'd hope.  The words 'to' and 'from' are what split
+// them up, so a name that has one of those in it needs quotes:
+// 'Add "Tom from Accounts" to Sales'.
+//
+// Anywhere a command needs someone who already works here, they can be given
+// by name or by number ('#4'), since names aren't always unique.
+use super::directory::Field;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Add {
+        name: String,
+        department: String,
+    },
+    // The department is only checked, since everyone is in just one
+    Remove {
+        name: String,
+        department: Option<String>,
+    },
+    // Like Remove, 'from' is only checked
+    Move {
+        name: String,
+        from: Option<String>,
+        to: String,
+    },
+    Rename {
+        department: String,
+        new_name: String,
+    },
+    Find {
+        name: String,
+    },
+    Show {
+        name: String,
+    },
+    Set {
+        field: Field,
+        name: String,
+        value: String,
+    },
+    Clear {
+        field: Field,
+        name: String,
+    },
+    List {
+        department: String,
+    },
+    All,
+    // Everyone whose name, or a word in it, starts with the prefix
+    Search {
+        prefix: String,
+    },
+    // How many people are in each department
+    Count,
+    // Without a department, the tree is for the whole company
+    Tree {
+        department: Option<String>,
+    },
+    Chain {
+        name: String,
+    },
+    // Without a file, these use the one the org chart was last loaded from or
+    // saved to
+    Save {
+        file: Option<String>,
+    },
+    Load {
+        file: Option<String>,
+    },
+    // A dry run shows what the import would change without changing it
+    Import {
+        file: String,
+        dry_run: bool,
+    },
+    Export {
+        file: String,
+    },
+    Undo,
+    Redo,
+    History,
+    Help,
+    Quit,
+}
+
+impl Command {
+    // Whether the command changes the directory, and so is kept in the
+    // history to be undone.  Load replaces the history along with everything
+    // else, so it isn't.
+    pub fn changes_directory(&self) -> bool {
+        matches!(
+            self,
+            Command::Add { .. }
+                | Command::Remove { .. }
+                | Command::Move { .. }
+                | Command::Rename { .. }
+                | Command::Set { .. }
+                | Command::Clear { .. }
+                | Command::Import { dry_run: false, .. }
+        )
+    }
+
+    // Whether the command lists employees, which --json writes as JSON
+    pub fn lists_employees(&self) -> bool {
+        matches!(
+            self,
+            Command::List { .. } | Command::All | Command::Search { .. }
+        )
+    }
+}
+
+// One word of input.  Quoted words are never taken to be keywords, so
+// '"to"' is just the word to.
+#[derive(Debug, PartialEq)]
+struct Word {
+    text: String,
+    quoted: bool,
+}
+
+impl Word {
+    fn is(&self, keyword: &str) -> bool {
+        !self.quoted && self.text.eq_ignore_ascii_case(keyword)
+    }
+}
+
+fn split_words(line: &str) -> Result<Vec<Word>, String> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    // \" for a quote inside quotes, and \\ for a backslash
+                    Some('\\') => match chars.next() {
+                        Some(escaped) => text.push(escaped),
+                        None => {
+                            return Err("There's a \\ at the end with nothing after it".to_string())
+                        }
+                    },
+                    Some(c) => text.push(c),
+                    None => return Err("A quote was opened but never closed".to_string()),
+                }
+            }
+            words.push(Word { text, quoted: true });
+        } else {
+            let mut text = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+            words.push(Word {
+                text,
+                quoted: false,
+            });
+        }
+    }
+    Ok(words)
+}
+
+// Joins words back into a name, or says which part of the command is missing
+fn join(words: &[Word], what: &str, usage: &str) -> Result<String, String> {
+    if words.is_empty() || words.iter().all(|word| word.text.is_empty()) {
+        return Err(format!("{} is missing.  Try '{}'", what, usage));
+    }
+    Ok(words
+        .iter()
+        .map(|word| word.text.as_str())
+        .collect::<Vec<&str>>()
+        .join(" "))
+}
+
+// Splits words at the first unquoted 'keyword'
+fn split_at<'a>(
+    words: &'a [Word],
+    keyword: &str,
+    usage: &str,
+) -> Result<(&'a [Word], &'a [Word]), String> {
+    match words.iter().position(|word| word.is(keyword)) {
+        Some(index) => Ok((&words[..index], &words[index + 1..])),
+        None => Err(format!("Expected '{}'.  Try '{}'", keyword, usage)),
+    }
+}
+
+// The '<field> of' at the start of Set and Clear
+fn split_field<'a>(words: &'a [Word], usage: &str) -> Result<(Field, &'a [Word]), String> {
+    match words {
+        [field, of, rest @ ..] if of.is("of") => match Field::parse(&field.text) {
+            Some(parsed) => Ok((parsed, rest)),
+            None => Err(format!(
+                "'{}' isn't something that can be set.  Try title, start or manager",
+                field.text
+            )),
+        },
+        _ => Err(format!("Expected '<field> of'.  Try '{}'", usage)),
+    }
+}
+
+impl Command {
+    pub fn parse_line(line: &str) -> Result<Command, String> {
+        let words = split_words(line)?;
+        let (first, rest) = match words.split_first() {
+            Some(split) => split,
+            None => return Err("Type a command, or 'Help' to see them all".to_string()),
+        };
+        if first.quoted {
+            return Err(format!("'{}' isn't a command", first.text));
+        }
+
+        match first.text.to_lowercase().as_str() {
+            "add" => {
+                let usage = "Add <name> to <department>";
+                let (name, department) = split_at(rest, "to", usage)?;
+                Ok(Command::Add {
+                    name: join(name, "The name", usage)?,
+                    department: join(department, "The department", usage)?,
+                })
+            }
+            "remove" => {
+                let usage = "Remove <name> [from <department>]";
+                let (name, department) = match split_at(rest, "from", usage) {
+                    Ok((name, department)) => {
+                        (name, Some(join(department, "The department", usage)?))
+                    }
+                    Err(_) => (rest, None),
+                };
+                Ok(Command::Remove {
+                    name: join(name, "The name", usage)?,
+                    department,
+                })
+            }
+            "move" => {
+                let usage = "Move <name> [from <department>] to <department>";
+                let (before_to, to) = split_at(rest, "to", usage)?;
+                let (name, from) = match split_at(before_to, "from", usage) {
+                    Ok((name, from)) => (name, Some(join(from, "The old department", usage)?)),
+                    Err(_) => (before_to, None),
+                };
+                Ok(Command::Move {
+                    name: join(name, "The name", usage)?,
+                    from,
+                    to: join(to, "The new department", usage)?,
+                })
+            }
+            "rename" => {
+                let usage = "Rename <department> to <new name>";
+                let (department, new_name) = split_at(rest, "to", usage)?;
+                Ok(Command::Rename {
+                    department: join(department, "The department", usage)?,
+                    new_name: join(new_name, "The new name", usage)?,
+                })
+            }
+            "find" => Ok(Command::Find {
+                name: join(rest, "The name", "Find <name>")?,
+            }),
+            "show" => Ok(Command::Show {
+                name: join(rest, "The name", "Show <name>")?,
+            }),
+            "set" => {
+                let usage = "Set <title|start|manager> of <name> to <value>";
+                let (field, rest) = split_field(rest, usage)?;
+                let (name, value) = split_at(rest, "to", usage)?;
+                Ok(Command::Set {
+                    field,
+                    name: join(name, "The name", usage)?,
+                    value: join(value, "The value", usage)?,
+                })
+            }
+            "clear" => {
+                let usage = "Clear <title|start|manager> of <name>";
+                let (field, name) = split_field(rest, usage)?;
+                Ok(Command::Clear {
+                    field,
+                    name: join(name, "The name", usage)?,
+                })
+            }
+            "list" => Ok(Command::List {
+                department: join(rest, "The department", "List <department>")?,
+            }),
+            "search" => Ok(Command::Search {
+                prefix: join(rest, "The start of the name", "Search <prefix>")?,
+            }),
+            "tree" => Ok(Command::Tree {
+                department: match rest {
+                    [] => None,
+                    _ => Some(join(rest, "The department", "Tree [department]")?),
+                },
+            }),
+            "chain" => Ok(Command::Chain {
+                name: join(rest, "The name", "Chain <name>")?,
+            }),
+            "save" | "load" => {
+                let file = match rest {
+                    [] => None,
+                    [file] => Some(file.text.clone()),
+                    _ => {
+                        return Err(
+                            "Only one file can be given.  Put quotes around names with spaces"
+                                .to_string(),
+                        )
+                    }
+                };
+                if first.is("save") {
+                    Ok(Command::Save { file })
+                } else {
+                    Ok(Command::Load { file })
+                }
+            }
+            "import" => {
+                let usage = "Import <file.csv> [dry run]";
+                match rest {
+                    [file] => Ok(Command::Import {
+                        file: file.text.clone(),
+                        dry_run: false,
+                    }),
+                    [file, dry, run] if dry.is("dry") && run.is("run") => Ok(Command::Import {
+                        file: file.text.clone(),
+                        dry_run: true,
+                    }),
+                    [] => Err(format!("The file is missing.  Try '{}'", usage)),
+                    _ => Err(format!(
+                        "Expected one file, then maybe 'dry run'.  Try '{}'.  \
+                         Put quotes around names with spaces",
+                        usage
+                    )),
+                }
+            }
+            "export" => match rest {
+                [file] => Ok(Command::Export {
+                    file: file.text.clone(),
+                }),
+                [] => Err("The file is missing.  Try 'Export <file.csv>'".to_string()),
+                _ => Err(
+                    "Only one file can be given.  Put quotes around names with spaces".to_string(),
+                ),
+            },
+            "all" | "count" | "undo" | "redo" | "history" | "help" | "quit" if !rest.is_empty() => {
+                Err(format!("'{}' doesn't take anything after it", first.text))
+            }
+            "all" => Ok(Command::All),
+            "count" => Ok(Command::Count),
+            "undo" => Ok(Command::Undo),
+            "redo" => Ok(Command::Redo),
+            "history" => Ok(Command::History),
+            "help" => Ok(Command::Help),
+            "quit" => Ok(Command::Quit),
+            _ => Err(format!(
+                "'{}' isn't a command.  Type 'Help' to see them all",
+                first.text
+            )),
+        }
+    }
+}
+
+pub const HELP: &str = "\
+Add <name> to <department>        add an employee
+Remove <name> [from <department>] remove an employee
+Move <name> [from <department>] to <department>
+                                  move an employee to another department
+Rename <department> to <name>     rename a department
+Find <name>                       show everyone with that name
+Show <name>                       show everything about one employee
+Set <field> of <name> to <value>  set someone's title, start (date, like
+                                  2021-04-01) or manager
+Clear <field> of <name>           clear someone's title, start or manager
+List <department>                 list the employees of a department
+All                               list all employees by department
+Search <prefix>                   find everyone whose name starts with
+                                  <prefix>, in any case
+Count                             count the employees in each department
+Tree [department]                 show who reports to whom, for the whole
+                                  company or one department
+Chain <name>                      show everyone above someone, up to the top
+Save [file]                       save the org chart
+Load [file]                       load the org chart, replacing this one
+Import <file.csv> [dry run]       add or update employees from a spreadsheet,
+                                  or with 'dry run' show what that would do
+Export <file.csv>                 save everyone to a spreadsheet
+Undo                              undo the last change
+Redo                              redo the last change that was undone
+History                           list the changes that can be undone or
+                                  redone
+Help                              show this list
+Quit                              save and quit
+Names with 'to' or 'from' in them can be put in double quotes.  Where a
+name is shared, use the employee's number instead, like #4.";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_word_and_quoted_names() {
+        assert_eq!(
+            Ok(Command::Add {
+                name: "Mary Ann".to_string(),
+                department: "Human Resources".to_string()
+            }),
+            Command::parse_line("add Mary Ann to Human Resources")
+        );
+        assert_eq!(
+            Ok(Command::Move {
+                name: "Tom from Accounts".to_string(),
+                from: None,
+                to: "Sales".to_string()
+            }),
+            Command::parse_line("Move \"Tom from Accounts\" to Sales")
+        );
+        assert_eq!(
+            Ok(Command::Move {
+                name: "Sue".to_string(),
+                from: Some("Analytics".to_string()),
+                to: "Sales".to_string()
+            }),
+            Command::parse_line("Move Sue from Analytics to Sales")
+        );
+        assert_eq!(
+            Ok(Command::Set {
+                field: Field::Title,
+                name: "#4".to_string(),
+                value: "Head of Sales".to_string()
+            }),
+            Command::parse_line("Set title of #4 to Head of Sales")
+        );
+        assert_eq!(
+            Ok(Command::Save {
+                file: Some("my org.txt".to_string())
+            }),
+            Command::parse_line("Save \"my org.txt\"")
+        );
+        assert_eq!(
+            Ok(Command::Import {
+                file: "hr list.csv".to_string(),
+                dry_run: true
+            }),
+            Command::parse_line("Import \"hr list.csv\" dry run")
+        );
+    }
+
+    #[test]
+    fn specific_errors() {
+        assert_eq!(
+            Err("Expected 'to'.  Try 'Add <name> to <department>'".to_string()),
+            Command::parse_line("Add Sally Engineering")
+        );
+        assert_eq!(
+            Err("The department is missing.  Try 'Remove <name> [from <department>]'".to_string()),
+            Command::parse_line("Remove Sally from")
+        );
+        assert_eq!(
+            Err(
+                "'salary' isn't something that can be set.  Try title, start or manager"
+                    .to_string()
+            ),
+            Command::parse_line("Set salary of Sally to 100")
+        );
+        assert_eq!(
+            Err("A quote was opened but never closed".to_string()),
+            Command::parse_line("Find \"Sally")
+        );
+        assert_eq!(
+            Err("'Hire' isn't a command.  Type 'Help' to see them all".to_string()),
+            Command::parse_line("Hire Sally")
+        );
+        assert_eq!(
+            Err("'All' doesn't take anything after it".to_string()),
+            Command::parse_line("All Sales")
+        );
+    }
+}
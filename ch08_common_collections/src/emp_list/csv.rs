@@ -0,0 +1,403 @@
+// Importing employees from a spreadsheet saved as CSV, and exporting them to
+// one.  The first row of the file names the columns, and we work out which
+// column is which from those names, so they can come in any order and HR's
+// 'Full Name' and 'Dept' work as well as our own 'name' and 'department'.
+// Columns we don't know are left alone.
+//
+// Each row is an employee.  A row with an id (which an exported file has) is
+// that employee.  Without one, it's whoever already has that name in that
+// department, and anyone else is added as someone new.  That way importing
+// the same file twice doesn't add everyone twice.
+//
+// Nothing is changed unless every row is fine.  Otherwise all of the
+// problems are listed, by row number as a spreadsheet would show it, so they
+// can be fixed in one go.
+use std::collections::HashMap;
+
+use super::directory::{Date, Directory, Employee};
+use super::history::{Change, Operation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Column {
+    Id,
+    Name,
+    Department,
+    Title,
+    Start,
+    Manager,
+}
+
+impl Column {
+    // The headers each column goes by.  Case, spaces and underscores don't
+    // matter, so 'Start Date' and 'start_date' are both 'startdate'.
+    fn from_header(header: &str) -> Option<Column> {
+        let header: String = header
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '_')
+            .flat_map(char::to_lowercase)
+            .collect();
+        match header.as_str() {
+            "id" | "number" | "employeeid" | "#" => Some(Column::Id),
+            "name" | "fullname" | "employee" | "employeename" => Some(Column::Name),
+            "department" | "dept" | "team" => Some(Column::Department),
+            "title" | "jobtitle" | "role" | "position" => Some(Column::Title),
+            "start" | "startdate" | "started" | "hiredate" => Some(Column::Start),
+            "manager" | "reportsto" | "supervisor" => Some(Column::Manager),
+            _ => None,
+        }
+    }
+}
+
+// What an import did, or would do in a dry run
+#[derive(Debug, PartialEq)]
+pub struct Imported {
+    pub changes: Vec<Change>,
+    // Headers that didn't match any column
+    pub ignored: Vec<String>,
+}
+
+// Splits CSV text into rows of fields.  Fields in double quotes can have
+// commas and new lines in them, and "" inside quotes is one ".
+fn parse(text: &str) -> Result<Vec<Vec<String>>, String> {
+    // Spreadsheets like to start the file with a byte order mark
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(format!(
+            "Row {} has a quote that's never closed",
+            rows.len() + 1
+        ));
+    }
+    // The last row might not end with a new line
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+// Imports the rows of 'text' into the directory.  The changes are what was
+// changed, for a dry run to show, and they can be undone like any other
+// command's.
+pub fn import(directory: &mut Directory, text: &str) -> Result<Imported, Vec<String>> {
+    let rows = parse(text).map_err(|e| vec![e])?;
+    let (header, rows) = match rows.split_first() {
+        Some(split) => split,
+        None => return Err(vec!["The file is empty".to_string()]),
+    };
+
+    let mut columns = HashMap::new();
+    let mut ignored = Vec::new();
+    for (index, name) in header.iter().enumerate() {
+        match Column::from_header(name) {
+            Some(column) => {
+                if columns.insert(column, index).is_some() {
+                    return Err(vec![format!("There are two {} columns", name)]);
+                }
+            }
+            None => ignored.push(name.clone()),
+        }
+    }
+    if !columns.contains_key(&Column::Name) || !columns.contains_key(&Column::Department) {
+        return Err(vec![
+            "The first row needs a name column and a department column".to_string(),
+        ]);
+    }
+
+    // Everything happens to a copy, which only replaces the real directory
+    // if every row was fine
+    let mut work = directory.clone();
+    let mut errors = Vec::new();
+    // Managers are done once everyone is in, since someone's manager can be
+    // further down the file
+    let mut managers = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        // The header is row 1
+        let row_number = index + 2;
+        let cell = |column| {
+            columns
+                .get(&column)
+                .map(|&index| row.get(index).map_or("", |cell| cell.trim()))
+        };
+        // Blank lines at the end of a spreadsheet are common
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        match import_row(&mut work, &cell) {
+            Ok(id) => {
+                if let Some(manager) = cell(Column::Manager) {
+                    managers.push((row_number, id, manager));
+                }
+            }
+            Err(e) => errors.push(format!("Row {}: {}", row_number, e)),
+        }
+    }
+    for (row_number, id, manager) in managers {
+        let manager = match manager {
+            "" => Ok(None),
+            _ => work.resolve(manager).map(Some),
+        };
+        if let Err(e) = manager.and_then(|manager| work.set_manager(id, manager)) {
+            errors.push(format!("Row {}: {}", row_number, e));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let changes = Operation::between("", directory, &work).changes;
+    *directory = work;
+    Ok(Imported { changes, ignored })
+}
+
+// Adds or updates the employee in one row, returning their id
+fn import_row<'a>(
+    directory: &mut Directory,
+    cell: &impl Fn(Column) -> Option<&'a str>,
+) -> Result<u32, String> {
+    let name = cell(Column::Name).unwrap_or("");
+    let department = cell(Column::Department).unwrap_or("");
+    if name.is_empty() || department.is_empty() {
+        return Err("every employee needs a name and a department".to_string());
+    }
+    // The org chart file keeps each of these on a line of its own, so a
+    // quoted cell that goes over more than one line can't be saved
+    let has_line_break = [Column::Name, Column::Department, Column::Title]
+        .iter()
+        .filter_map(|&column| cell(column))
+        .any(|text| text.contains(&['\n', '\r'][..]));
+    if has_line_break {
+        return Err("names, departments and titles can't have line breaks".to_string());
+    }
+    // Checked before anything changes, so a bad date doesn't leave someone
+    // half imported
+    let start_date = match cell(Column::Start) {
+        Some("") => Some(None),
+        Some(start) => Some(Some(Date::parse(start)?)),
+        None => None,
+    };
+
+    let id = match cell(Column::Id).filter(|id| !id.is_empty()) {
+        Some(id) => {
+            let number = id.strip_prefix('#').unwrap_or(id);
+            number
+                .parse()
+                .ok()
+                .filter(|id| directory.get(*id).is_some())
+                .ok_or_else(|| format!("there's no employee {}", id))?
+        }
+        None => {
+            let existing = directory
+                .list(department)
+                .into_iter()
+                .filter(|e| e.name == name)
+                .map(|e| e.id)
+                .collect::<Vec<u32>>();
+            match existing.as_slice() {
+                [] => directory.add(name, department),
+                [id] => *id,
+                _ => {
+                    return Err(format!(
+                        "there's more than one {} in {}, so this row needs an id",
+                        name, department
+                    ))
+                }
+            }
+        }
+    };
+
+    let employee = directory.get_mut(id).unwrap();
+    employee.name = name.to_string();
+    employee.department = department.to_string();
+    // An empty cell clears the field, but a column that isn't there at all
+    // leaves it as it was
+    if let Some(title) = cell(Column::Title) {
+        employee.title = Some(title.to_string()).filter(|title| !title.is_empty());
+    }
+    if let Some(start_date) = start_date {
+        employee.start_date = start_date;
+    }
+    Ok(id)
+}
+
+// Everyone, in the same columns an import looks for.  Managers are given by
+// name where that's enough to tell who they are, and by number where it isn't.
+pub fn export(directory: &Directory) -> String {
+    let mut text = String::from("id,name,department,title,start,manager\n");
+    for employee in directory.employees() {
+        let manager = match employee.manager.and_then(|id| directory.get(id)) {
+            Some(manager) if directory.named(&manager.name).len() == 1 => manager.name.clone(),
+            Some(manager) => format!("#{}", manager.id),
+            None => String::new(),
+        };
+        let fields = [
+            employee.id.to_string(),
+            employee.name.clone(),
+            employee.department.clone(),
+            employee.title.clone().unwrap_or_default(),
+            employee
+                .start_date
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+            manager,
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| quote(field)).collect();
+        text.push_str(&fields.join(","));
+        text.push('\n');
+    }
+    text
+}
+
+// Only fields that need quotes get them, which is what spreadsheets do too
+fn quote(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// A line for each change an import made, or would make, like
+// 'add #5 Sally (Engineering)' or 'change #4 Sue: title Rep -> Head of Sales'
+pub fn describe(directory: &Directory, change: &Change) -> String {
+    match (&change.before, &change.after) {
+        (None, Some(after)) => format!("add {}", directory.summary(after)),
+        (Some(before), None) => format!("remove {}", directory.summary(before)),
+        (Some(before), Some(after)) => {
+            let manager = |employee: &Employee| {
+                employee
+                    .manager
+                    .and_then(|id| directory.get(id))
+                    .map(|manager| format!("{} (#{})", manager.name, manager.id))
+            };
+            let date = |employee: &Employee| employee.start_date.map(|date| date.to_string());
+            let fields = [
+                ("name", Some(before.name.clone()), Some(after.name.clone())),
+                (
+                    "department",
+                    Some(before.department.clone()),
+                    Some(after.department.clone()),
+                ),
+                ("title", before.title.clone(), after.title.clone()),
+                ("start", date(before), date(after)),
+                ("manager", manager(before), manager(after)),
+            ];
+            let differences: Vec<String> = fields
+                .iter()
+                .filter(|(_, was, is)| was != is)
+                .map(|(field, was, is)| {
+                    let show = |value: &Option<String>| value.clone().unwrap_or("-".to_string());
+                    format!("{} {} -> {}", field, show(was), show(is))
+                })
+                .collect();
+            format!(
+                "change #{} {}: {}",
+                after.id,
+                before.name,
+                differences.join(", ")
+            )
+        }
+        (None, None) => unreachable!("a change always has a before or an after"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_fields() {
+        assert_eq!(
+            Ok(vec![
+                vec!["name".to_string(), "title".to_string()],
+                vec!["Sue".to_string(), "Head of \"Sales\", West".to_string()],
+                vec!["Joe".to_string(), "two\nlines".to_string()],
+            ]),
+            parse("\u{feff}name,title\r\nSue,\"Head of \"\"Sales\"\", West\"\nJoe,\"two\nlines\"")
+        );
+        assert!(parse("name\n\"Sue\n").is_err());
+    }
+
+    #[test]
+    fn imports_and_exports() {
+        let mut directory = Directory::new();
+        let amir = directory.add("Amir", "Sales");
+        let text = "Full Name,Dept,Job Title,Start Date,Reports To,Salary\n\
+                    Amir,Sales,Head of Sales,,,100\n\
+                    Sue,Sales,\"Rep, West\",2021-04-01,Amir,50\n";
+        let imported = import(&mut directory, text).unwrap();
+        assert_eq!(vec!["Salary".to_string()], imported.ignored);
+        assert_eq!(
+            vec![
+                "change #1 Amir: title - -> Head of Sales".to_string(),
+                "add #2 Sue, Rep, West (Sales)".to_string()
+            ],
+            imported
+                .changes
+                .iter()
+                .map(|change| describe(&directory, change))
+                .collect::<Vec<String>>()
+        );
+        assert_eq!(Some(amir), directory.get(2).unwrap().manager);
+
+        let exported = export(&directory);
+        assert_eq!(
+            "id,name,department,title,start,manager\n\
+             1,Amir,Sales,Head of Sales,,\n\
+             2,Sue,Sales,\"Rep, West\",2021-04-01,Amir\n",
+            exported
+        );
+        // Importing what was exported changes nothing
+        let before = directory.clone();
+        assert!(import(&mut directory, &exported)
+            .unwrap()
+            .changes
+            .is_empty());
+        assert_eq!(before, directory);
+
+        // Every bad row is reported, and nothing is changed
+        let text = "id,name,department,start,manager\n\
+                    ,Bob,,,\n\
+                    9,Joe,Sales,,\n\
+                    ,Joe,Sales,2021-02-30,\n\
+                    ,\"Ann\nLee\",Sales,,\n\
+                    1,Amir,Sales,,Sue\n";
+        assert_eq!(
+            Err(vec![
+                "Row 2: every employee needs a name and a department".to_string(),
+                "Row 3: there's no employee 9".to_string(),
+                "Row 4: '2021-02-30' isn't a date.  Write it like 2021-04-01".to_string(),
+                "Row 5: names, departments and titles can't have line breaks".to_string(),
+                "Row 6: Sue (#2) already reports to Amir (#1), so that would go round in a circle"
+                    .to_string(),
+            ]),
+            import(&mut directory, text)
+        );
+        assert_eq!(before, directory);
+    }
+}
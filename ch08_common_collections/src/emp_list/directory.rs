@@ -0,0 +1,545 @@
+// Everyone in the company, one Employee each.  Two people can share a name,
+// so each employee is given a number when they're added that stays theirs
+// until they leave, and that's what the directory goes by.  In commands you
+// can use either: 'Sally' works as long as there's only one Sally, and '#4'
+// always means employee 4.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Employee {
+    pub id: u32,
+    pub name: String,
+    pub department: String,
+    pub title: Option<String>,
+    pub start_date: Option<Date>,
+    // The id of the employee this one reports to
+    pub manager: Option<u32>,
+}
+
+// A calendar date.  Deriving PartialOrd compares the fields in order, which
+// for year, month, day is the same as comparing the dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    // Dates are written the ISO way, 2021-04-01, so there's no wondering
+    // which of the numbers is the month
+    pub fn parse(text: &str) -> Result<Date, String> {
+        let bad = || format!("'{}' isn't a date.  Write it like 2021-04-01", text);
+        let mut parts = text.splitn(3, '-');
+        let mut next = || parts.next().unwrap_or("");
+        // A year too big for an i32 fails here rather than wrapping round
+        let (year, month, day) = match (
+            next().parse::<i32>(),
+            next().parse::<u32>(),
+            next().parse::<u32>(),
+        ) {
+            (Ok(year), Ok(month), Ok(day)) => (year, month, day),
+            _ => return Err(bad()),
+        };
+        let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+        let days_in_month = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return Err(bad()),
+        };
+        if day == 0 || day > days_in_month {
+            return Err(bad());
+        }
+        Ok(Date { year, month, day })
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+// The fields that Set and Clear can change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Title,
+    Start,
+    Manager,
+}
+
+impl Field {
+    pub fn parse(word: &str) -> Option<Field> {
+        match word.to_lowercase().as_str() {
+            "title" => Some(Field::Title),
+            "start" => Some(Field::Start),
+            "manager" => Some(Field::Manager),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directory {
+    // A BTreeMap keeps everyone in the order they were added
+    pub(super) employees: BTreeMap<u32, Employee>,
+    // Numbers aren't reused, even once the person they belonged to has left
+    pub(super) next_id: u32,
+}
+
+// Deriving Default would start the numbers at 0, but they start at 1
+impl Default for Directory {
+    fn default() -> Directory {
+        Directory::new()
+    }
+}
+
+impl Directory {
+    pub fn new() -> Directory {
+        Directory {
+            employees: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+
+    // Everyone in every department, by department and then like list
+    pub fn all(&self) -> Vec<&Employee> {
+        self.departments()
+            .into_iter()
+            .flat_map(|department| self.list(department))
+            .collect()
+    }
+
+    pub fn add(&mut self, name: &str, department: &str) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.employees.insert(
+            id,
+            Employee {
+                id,
+                name: name.to_string(),
+                department: department.to_string(),
+                title: None,
+                start_date: None,
+                manager: None,
+            },
+        );
+        id
+    }
+
+    // Anyone who reported to the person leaving is left without a manager
+    pub fn remove(&mut self, id: u32) -> Option<Employee> {
+        let removed = self.employees.remove(&id)?;
+        for employee in self.employees.values_mut() {
+            if employee.manager == Some(id) {
+                employee.manager = None;
+            }
+        }
+        Some(removed)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Employee> {
+        self.employees.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Employee> {
+        self.employees.get_mut(&id)
+    }
+
+    pub fn employees(&self) -> impl Iterator<Item = &Employee> {
+        self.employees.values()
+    }
+
+    // Works out who 'who' means: '#4' is employee 4, and anything else is a
+    // name, which has to belong to exactly one person
+    pub fn resolve(&self, who: &str) -> Result<u32, String> {
+        if let Some(number) = who.strip_prefix('#') {
+            return number
+                .parse()
+                .ok()
+                .filter(|id| self.employees.contains_key(id))
+                .ok_or_else(|| format!("There's no employee {}", who));
+        }
+        let found = self.named(who);
+        match found.as_slice() {
+            [] => Err(format!("No one called {} works here", who)),
+            [employee] => Ok(employee.id),
+            _ => {
+                let everyone: Vec<String> = found
+                    .iter()
+                    .map(|e| format!("#{} in {}", e.id, e.department))
+                    .collect();
+                Err(format!(
+                    "There are {} people called {} ({}).  Use their number instead",
+                    found.len(),
+                    who,
+                    everyone.join(", ")
+                ))
+            }
+        }
+    }
+
+    pub fn named(&self, name: &str) -> Vec<&Employee> {
+        self.employees().filter(|e| e.name == name).collect()
+    }
+
+    // Departments only exist while someone works in them, so these are
+    // worked out from the employees.  A BTreeSet gives them in sorted order
+    pub fn departments(&self) -> Vec<&str> {
+        let departments: BTreeSet<&str> = self.employees().map(|e| e.department.as_str()).collect();
+        departments.into_iter().collect()
+    }
+
+    // How many people work in each department, in the same order as
+    // departments
+    pub fn counts(&self) -> Vec<(&str, usize)> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for employee in self.employees() {
+            *counts.entry(&employee.department).or_insert(0) += 1;
+        }
+        counts.into_iter().collect()
+    }
+
+    // Everyone with a name, or a word in their name, that starts with
+    // 'prefix', ignoring case.  Lowercasing is done the Unicode way, so
+    // 'émi' finds Émile.  Names are compared a grapheme (what you'd think of
+    // as one character) at a time, so 'e' doesn't find an 'é' that's written
+    // as an e followed by an accent.
+    pub fn search(&self, prefix: &str) -> Vec<&Employee> {
+        let prefix = prefix.to_lowercase();
+        let prefix: Vec<&str> = prefix.graphemes(true).collect();
+        let starts_with = |word: &str| {
+            let word = word.to_lowercase();
+            let mut graphemes = word.graphemes(true);
+            prefix.iter().all(|g| graphemes.next() == Some(*g))
+        };
+        let mut found: Vec<&Employee> = self
+            .employees()
+            .filter(|e| starts_with(&e.name) || e.name.split_whitespace().any(starts_with))
+            .collect();
+        found.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id)));
+        found
+    }
+
+    // The department with the name nearest to 'name', for when someone asks
+    // for one that doesn't exist.  'Nearest' is the fewest letters added,
+    // removed or changed to get from one name to the other, ignoring case,
+    // and anything more than a third of the name away isn't a good guess.
+    pub fn closest_department(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        let allowed = (name.chars().count() / 3).max(1);
+        self.departments()
+            .into_iter()
+            .map(|department| (edit_distance(&name, &department.to_lowercase()), department))
+            .filter(|(distance, _)| *distance <= allowed)
+            .min()
+            .map(|(_, department)| department)
+    }
+
+    // Everyone in one department, sorted by name, and people with the same
+    // name by number
+    pub fn list(&self, department: &str) -> Vec<&Employee> {
+        let mut employees: Vec<&Employee> = self
+            .employees()
+            .filter(|e| e.department == department)
+            .collect();
+        employees.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id)));
+        employees
+    }
+
+    pub fn rename_department(&mut self, department: &str, new_name: &str) -> Result<(), String> {
+        if self.employees().any(|e| e.department == new_name) {
+            return Err(format!("There's already a department called {}", new_name));
+        }
+        let mut found = false;
+        for employee in self.employees.values_mut() {
+            if employee.department == department {
+                employee.department = new_name.to_string();
+                found = true;
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(format!("There's no department called {}", department))
+        }
+    }
+
+    // Reporting lines can't go round in a circle, or there'd be no one at the
+    // top of them.  So before 'id' reports to 'manager', we check that
+    // 'manager' doesn't already report to 'id', however far up that goes.
+    pub fn set_manager(&mut self, id: u32, manager: Option<u32>) -> Result<(), String> {
+        if let Some(manager) = manager {
+            if manager == id {
+                return Err("No one can be their own manager".to_string());
+            }
+            if self.reports_to(manager, id) {
+                return Err(format!(
+                    "{} already reports to {}, so that would go round in a circle",
+                    self.name_of(manager),
+                    self.name_of(id)
+                ));
+            }
+        }
+        if let Some(employee) = self.employees.get_mut(&id) {
+            employee.manager = manager;
+        }
+        Ok(())
+    }
+
+    // Whether 'id' reports to 'manager', directly or through other managers.
+    // A hand-edited file could already have a circle in it, so we keep track
+    // of who we've seen rather than trusting the walk up to end.
+    pub fn reports_to(&self, id: u32, manager: u32) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut current = self.get(id).and_then(|e| e.manager);
+        while let Some(above) = current {
+            if above == manager {
+                return true;
+            }
+            if !seen.insert(above) {
+                return false;
+            }
+            current = self.get(above).and_then(|e| e.manager);
+        }
+        false
+    }
+
+    // The people 'id' reports to, starting with their own manager and going
+    // up to whoever is at the top
+    pub fn chain_of_command(&self, id: u32) -> Vec<&Employee> {
+        let mut chain: Vec<&Employee> = Vec::new();
+        let mut current = self.get(id).and_then(|e| e.manager);
+        while let Some(manager) = current.and_then(|id| self.get(id)) {
+            if manager.id == id || chain.iter().any(|e| e.id == manager.id) {
+                break;
+            }
+            chain.push(manager);
+            current = manager.manager;
+        }
+        chain
+    }
+
+    // Everyone who reports straight to 'id', sorted like list
+    pub fn reports(&self, id: u32) -> Vec<&Employee> {
+        let mut reports: Vec<&Employee> =
+            self.employees().filter(|e| e.manager == Some(id)).collect();
+        reports.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id)));
+        reports
+    }
+
+    // The reporting lines as an indented tree, each person's reports under
+    // them:
+    //
+    //     #2 Joe, Head of Engineering (Engineering)
+    //       #1 Sally, Engineer (Engineering)
+    //       #3 Bob (Engineering)
+    //
+    // For the whole company, the tree starts from everyone without a
+    // manager.  For one department it only has the people in it, starting
+    // from those whose manager (if any) is somewhere else.
+    pub fn tree(&self, department: Option<&str>) -> String {
+        let in_tree = |e: &Employee| department.is_none_or(|d| e.department == d);
+        let mut tops: Vec<&Employee> = self
+            .employees()
+            .filter(|e| in_tree(e))
+            .filter(|e| {
+                e.manager
+                    .and_then(|id| self.get(id))
+                    .is_none_or(|manager| !in_tree(manager))
+            })
+            .collect();
+        tops.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id)));
+
+        let mut tree = String::new();
+        // (employee, how deep they are), worked through like a stack so that
+        // each person's reports come straight after them
+        let mut stack: Vec<(&Employee, usize)> = tops.into_iter().rev().map(|e| (e, 0)).collect();
+        while let Some((employee, depth)) = stack.pop() {
+            tree.push_str(&format!(
+                "{}{}\n",
+                "  ".repeat(depth),
+                self.summary(employee)
+            ));
+            for report in self.reports(employee.id).into_iter().rev() {
+                if in_tree(report) {
+                    stack.push((report, depth + 1));
+                }
+            }
+        }
+        tree
+    }
+
+    fn name_of(&self, id: u32) -> String {
+        match self.get(id) {
+            Some(employee) => format!("{} (#{})", employee.name, employee.id),
+            None => format!("#{}", id),
+        }
+    }
+
+    // An employee with their title and department, for the tree and the
+    // chain of command, where who they report to is already shown
+    pub fn summary(&self, employee: &Employee) -> String {
+        let mut summary = format!("#{} {}", employee.id, employee.name);
+        if let Some(title) = &employee.title {
+            summary.push_str(&format!(", {}", title));
+        }
+        summary.push_str(&format!(" ({})", employee.department));
+        summary
+    }
+
+    // One line about an employee, like
+    // '#4 Sally, Engineer, started 2021-04-01, reports to Joe (#2)'
+    pub fn describe(&self, employee: &Employee) -> String {
+        let mut description = format!("#{} {}", employee.id, employee.name);
+        if let Some(title) = &employee.title {
+            description.push_str(&format!(", {}", title));
+        }
+        if let Some(start_date) = employee.start_date {
+            description.push_str(&format!(", started {}", start_date));
+        }
+        if let Some(manager) = employee.manager {
+            description.push_str(&format!(", reports to {}", self.name_of(manager)));
+        }
+        description
+    }
+}
+
+// The Levenshtein distance between two strings.  Each row holds how far the
+// start of 'a' is from each start of 'b', built from the row before it.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            let deletion = previous[j + 1] + 1;
+            let insertion = row[j] + 1;
+            row.push(substitution.min(deletion).min(insertion));
+        }
+        previous = row;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            Ok(Date {
+                year: 2024,
+                month: 2,
+                day: 29
+            }),
+            Date::parse("2024-02-29")
+        );
+        assert!(Date::parse("2023-02-29").is_err());
+        assert!(Date::parse("2023-13-01").is_err());
+        assert!(Date::parse("04/01/2021").is_err());
+        assert_eq!("0999-01-02", Date::parse("999-1-2").unwrap().to_string());
+        assert!(Date::parse("4294967295-01-01").is_err());
+    }
+
+    #[test]
+    fn resolves_names_and_numbers() {
+        let mut directory = Directory::new();
+        let sam = directory.add("Sam", "Sales");
+        directory.add("Sam", "Engineering");
+        let sally = directory.add("Sally", "Engineering");
+
+        assert_eq!(Ok(sally), directory.resolve("Sally"));
+        assert_eq!(Ok(sam), directory.resolve("#1"));
+        assert_eq!(
+            Err(
+                "There are 2 people called Sam (#1 in Sales, #2 in Engineering).  \
+                 Use their number instead"
+                    .to_string()
+            ),
+            directory.resolve("Sam")
+        );
+        assert!(directory.resolve("#9").is_err());
+
+        directory.set_manager(sally, Some(sam)).unwrap();
+        directory.remove(sam);
+        assert_eq!(None, directory.get(sally).unwrap().manager);
+        assert_eq!(4, directory.add("Sam", "Sales"));
+    }
+
+    #[test]
+    fn searches_and_suggestions() {
+        let mut directory = Directory::new();
+        directory.add("Émile Zola", "Engineering");
+        directory.add("Mary Ann", "Sales");
+        // An e with a combining accent, rather than é
+        directory.add("Rene\u{301}", "Sales");
+        directory.add("Renata", "Analytics");
+
+        let names = |found: Vec<&Employee>| -> Vec<String> {
+            found.iter().map(|e| e.name.clone()).collect()
+        };
+        assert_eq!(vec!["Émile Zola"], names(directory.search("éMI")));
+        assert_eq!(vec!["Émile Zola"], names(directory.search("zo")));
+        assert_eq!(vec!["Mary Ann"], names(directory.search("ann")));
+        assert_eq!(
+            vec!["Renata", "Rene\u{301}"],
+            names(directory.search("ren"))
+        );
+        assert!(directory.search("rene").is_empty());
+        assert_eq!(vec!["Rene\u{301}"], names(directory.search("rene\u{301}")));
+
+        assert_eq!(
+            vec![("Analytics", 1), ("Engineering", 1), ("Sales", 2)],
+            directory.counts()
+        );
+
+        assert_eq!(
+            Some("Engineering"),
+            directory.closest_department("enginering")
+        );
+        assert_eq!(Some("Sales"), directory.closest_department("SALES"));
+        assert_eq!(None, directory.closest_department("Marketing"));
+    }
+
+    #[test]
+    fn reporting_lines() {
+        let mut directory = Directory::new();
+        let amir = directory.add("Amir", "Sales");
+        let joe = directory.add("Joe", "Engineering");
+        let sally = directory.add("Sally", "Engineering");
+        let bob = directory.add("Bob", "Engineering");
+        directory.set_manager(joe, Some(amir)).unwrap();
+        directory.set_manager(sally, Some(joe)).unwrap();
+        directory.set_manager(bob, Some(joe)).unwrap();
+
+        assert_eq!(
+            Err(
+                "Sally (#3) already reports to Amir (#1), so that would go round in a circle"
+                    .to_string()
+            ),
+            directory.set_manager(amir, Some(sally))
+        );
+        let chain: Vec<u32> = directory
+            .chain_of_command(sally)
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(vec![joe, amir], chain);
+        assert_eq!(
+            "#1 Amir (Sales)\n  #2 Joe (Engineering)\n    #4 Bob (Engineering)\n    #3 Sally (Engineering)\n",
+            directory.tree(None)
+        );
+        assert_eq!(
+            "#2 Joe (Engineering)\n  #4 Bob (Engineering)\n  #3 Sally (Engineering)\n",
+            directory.tree(Some("Engineering"))
+        );
+    }
+}
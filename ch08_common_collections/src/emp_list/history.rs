@@ -0,0 +1,169 @@
+// Undo and Redo.  Every command that changes the directory is kept as an
+// Operation, which is what the employees it touched looked like before it ran
+// and after.  Undoing puts the 'before's back and redoing puts the 'after's
+// back, so every command can be reversed in the same way, without each one
+// needing its own opposite written for it.
+use super::directory::{Directory, Employee};
+
+// The oldest operations are forgotten once there are more than this, so the
+// file doesn't keep growing forever
+const LIMIT: usize = 100;
+
+// One employee touched by an operation.  No 'before' means the operation
+// added them, and no 'after' means it removed them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub id: u32,
+    pub before: Option<Employee>,
+    pub after: Option<Employee>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    // The command as it was typed
+    pub description: String,
+    pub changes: Vec<Change>,
+}
+
+impl Operation {
+    // Works out what a command did by comparing the directory from before it
+    // ran with the one after, employee by employee.
+    pub fn between(description: &str, before: &Directory, after: &Directory) -> Operation {
+        let mut ids: Vec<u32> = before
+            .employees
+            .keys()
+            .chain(after.employees.keys())
+            .copied()
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let changes = ids
+            .into_iter()
+            .filter_map(|id| {
+                let (was, is) = (before.get(id), after.get(id));
+                if was == is {
+                    return None;
+                }
+                Some(Change {
+                    id,
+                    before: was.cloned(),
+                    after: is.cloned(),
+                })
+            })
+            .collect();
+        Operation {
+            description: description.to_string(),
+            changes,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct History {
+    pub operations: Vec<Operation>,
+    // How many of the operations at the end have been undone, and so can be
+    // redone
+    pub undone: usize,
+}
+
+impl History {
+    // Commands that didn't change anything, like setting a title to what it
+    // already was, aren't worth undoing
+    pub fn record(&mut self, operation: Operation) {
+        if operation.changes.is_empty() {
+            return;
+        }
+        // Doing something new means whatever was undone can't be redone
+        self.operations.truncate(self.done());
+        self.undone = 0;
+        self.operations.push(operation);
+        if self.operations.len() > LIMIT {
+            self.operations.remove(0);
+        }
+    }
+
+    // How many operations are in effect
+    pub fn done(&self) -> usize {
+        self.operations.len() - self.undone
+    }
+
+    pub fn undo(&mut self, directory: &mut Directory) -> Result<&Operation, String> {
+        let done = self.done();
+        if done == 0 {
+            return Err("There's nothing to undo".to_string());
+        }
+        self.undone += 1;
+        let operation = &self.operations[done - 1];
+        for change in &operation.changes {
+            put(directory, change.id, &change.before);
+        }
+        Ok(operation)
+    }
+
+    pub fn redo(&mut self, directory: &mut Directory) -> Result<&Operation, String> {
+        if self.undone == 0 {
+            return Err("There's nothing to redo".to_string());
+        }
+        self.undone -= 1;
+        let operation = &self.operations[self.done() - 1];
+        for change in &operation.changes {
+            put(directory, change.id, &change.after);
+        }
+        Ok(operation)
+    }
+}
+
+// Numbers aren't reused, so redoing an Add puts the employee back under the
+// number they had, and next_id doesn't need to change
+fn put(directory: &mut Directory, id: u32, employee: &Option<Employee>) {
+    match employee {
+        Some(employee) => {
+            directory.employees.insert(id, employee.clone());
+        }
+        None => {
+            directory.employees.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undoes_and_redoes() {
+        let mut directory = Directory::new();
+        let mut history = History::default();
+
+        let before = directory.clone();
+        let joe = directory.add("Joe", "Engineering");
+        let sally = directory.add("Sally", "Engineering");
+        history.record(Operation::between("Add two", &before, &directory));
+        let added = directory.clone();
+        directory.set_manager(sally, Some(joe)).unwrap();
+        let before = directory.clone();
+        directory.remove(joe);
+        history.record(Operation::between("Remove Joe", &before, &directory));
+        // Sally's manager was cleared as well as Joe being removed
+        assert_eq!(2, history.operations[1].changes.len());
+
+        assert_eq!(
+            "Remove Joe",
+            history.undo(&mut directory).unwrap().description
+        );
+        assert_eq!(Some(joe), directory.get(sally).unwrap().manager);
+        history.undo(&mut directory).unwrap();
+        assert_eq!(Directory::new().employees, directory.employees);
+        assert!(history.undo(&mut directory).is_err());
+        history.redo(&mut directory).unwrap();
+        assert_eq!(added.employees, directory.employees);
+
+        // Something new can't be followed by redoing Remove Joe
+        let before = directory.clone();
+        directory.add("Bob", "Sales");
+        history.record(Operation::between("Add Bob to Sales", &before, &directory));
+        assert_eq!(2, history.operations.len());
+        assert!(history.redo(&mut directory).is_err());
+    }
+}
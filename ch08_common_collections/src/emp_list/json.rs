@@ -0,0 +1,74 @@
+// JSON for List and All in batch mode, so other programs can read the
+// results.  It's only ever a list of employees, which is little enough that
+// writing it out by hand is simpler than bringing in a crate for it:
+//
+//     [{"id":1,"name":"Sally","department":"Engineering","title":"Engineer",
+//       "start":"2021-04-01","manager":2}]
+//
+// all on one line.  Fields that aren't set are null.
+use super::directory::Employee;
+
+pub fn employees(employees: &[&Employee]) -> String {
+    let objects: Vec<String> = employees.iter().map(|employee| object(employee)).collect();
+    format!("[{}]", objects.join(","))
+}
+
+fn object(employee: &Employee) -> String {
+    let null = || "null".to_string();
+    format!(
+        "{{\"id\":{},\"name\":{},\"department\":{},\"title\":{},\"start\":{},\"manager\":{}}}",
+        employee.id,
+        string(&employee.name),
+        string(&employee.department),
+        employee.title.as_deref().map_or_else(null, string),
+        employee
+            .start_date
+            .map_or_else(null, |date| string(&date.to_string())),
+        employee
+            .manager
+            .map_or_else(null, |manager| manager.to_string()),
+    )
+}
+
+// A JSON string, with the characters JSON doesn't allow in one escaped
+fn string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emp_list::directory::{Date, Directory};
+
+    #[test]
+    fn writes_employees() {
+        let mut directory = Directory::new();
+        let joe = directory.add("Joe \"JJ\" Jones", "Engineering");
+        let sally = directory.add("Sally", "Engineering");
+        directory.get_mut(sally).unwrap().start_date = Date::parse("2021-04-01").ok();
+        directory.set_manager(sally, Some(joe)).unwrap();
+
+        let everyone: Vec<&Employee> = directory.employees().collect();
+        assert_eq!(
+            "[{\"id\":1,\"name\":\"Joe \\\"JJ\\\" Jones\",\"department\":\"Engineering\",\
+             \"title\":null,\"start\":null,\"manager\":null},\
+             {\"id\":2,\"name\":\"Sally\",\"department\":\"Engineering\",\
+             \"title\":null,\"start\":\"2021-04-01\",\"manager\":1}]",
+            employees(&everyone)
+        );
+        assert_eq!("[]", employees(&[]));
+    }
+}
@@ -0,0 +1,335 @@
+// Typing commands at a terminal.  This uses rustyline, a line editor like the
+// one shells have, so the arrow keys move around the line and through
+// earlier commands, those commands are kept between runs, and Tab finishes
+// off command words, departments and names from the org chart.
+//
+// Ctrl-C throws away the line being typed, and Ctrl-D saves and quits, the
+// same as Quit.
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+
+use super::directory::Directory;
+use super::Session;
+
+// Every command word, for completing the start of a line
+const COMMANDS: [&str; 23] = [
+    "Add", "All", "Chain", "Clear", "Count", "Export", "Find", "Help", "History", "Import", "List",
+    "Load", "Move", "Quit", "Redo", "Remove", "Rename", "Save", "Search", "Set", "Show", "Tree",
+    "Undo",
+];
+
+const FIELDS: [&str; 3] = ["title", "start", "manager"];
+
+// The words that split a command into parts
+const KEYWORDS: [&str; 3] = ["to", "from", "of"];
+
+pub fn run(session: &mut Session) -> rustyline::Result<()> {
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .auto_add_history(false)
+        .build();
+    let mut editor: Editor<OrgChartHelper, DefaultHistory> = Editor::with_config(config)?;
+    editor.set_helper(Some(OrgChartHelper::new(&session.directory)));
+    // Not having any commands from last time yet is fine
+    let _ = editor.load_history(&history_file(&session.file));
+
+    loop {
+        match editor.readline("> ") {
+            Ok(input) => {
+                if input.trim().is_empty() {
+                    continue;
+                }
+                editor.add_history_entry(input.as_str())?;
+                match session.run(&input) {
+                    Ok(Some(output)) => print!("{}", output),
+                    Ok(None) => break,
+                    Err(e) => println!("{}", e),
+                }
+                // The command may have added or moved people, so the
+                // completions need to catch up
+                editor.set_helper(Some(OrgChartHelper::new(&session.directory)));
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("(Type 'Quit' or press Ctrl-D to save and quit)");
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Save and Load can have changed which file we're working with, and the
+    // commands go with whichever it is now
+    let history_file = history_file(&session.file);
+    if let Err(e) = editor.save_history(&history_file) {
+        println!(
+            "Couldn't save the commands to {}: {}",
+            history_file.display(),
+            e
+        );
+    }
+    Ok(())
+}
+
+// The commands are kept next to the org chart, as emp_list.txt.history for
+// emp_list.txt.  Adding to the name rather than replacing the extension means
+// they can never be saved over the org chart itself.
+fn history_file(file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.history", file))
+}
+
+// What Tab can complete to.  It's a copy of the names in the org chart, since
+// rustyline holds on to the helper while the directory is being changed.
+struct OrgChartHelper {
+    departments: Vec<String>,
+    names: Vec<String>,
+}
+
+impl OrgChartHelper {
+    fn new(directory: &Directory) -> OrgChartHelper {
+        // Two people can share a name, but it only needs offering once
+        let names: BTreeSet<String> = directory.employees().map(|e| e.name.clone()).collect();
+        OrgChartHelper {
+            departments: directory
+                .departments()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            names: names.into_iter().collect(),
+        }
+    }
+}
+
+impl Completer for OrgChartHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&line[..pos], &self.departments, &self.names))
+    }
+}
+
+// rustyline wants these too, but we don't need hints, colours or checking
+// lines before they're entered
+impl Hinter for OrgChartHelper {
+    type Hint = String;
+}
+impl Highlighter for OrgChartHelper {}
+impl Validator for OrgChartHelper {}
+impl Helper for OrgChartHelper {}
+
+// One word of the line, and where it starts
+struct Word {
+    start: usize,
+    text: String,
+    quoted: bool,
+}
+
+// Works out what could finish off 'before', the line up to the cursor.  The
+// first word is a command, and after that it depends on the command and the
+// last 'to', 'from' or 'of': 'List Eng' finishes a department and 'Move Sa'
+// finishes a name.  Names can be more than one word, so everything since the
+// command or keyword is what's being finished.
+//
+// Returns where the part being finished starts, and what it could be.
+fn complete(before: &str, departments: &[String], names: &[String]) -> (usize, Vec<String>) {
+    let mut words = split(before);
+    // A line ending in a space (outside quotes) has started a new word
+    let open_quote = words
+        .last()
+        .is_some_and(|w| w.quoted && !before[w.start + 1..].contains('"'));
+    if before.trim().is_empty() || (before.ends_with(char::is_whitespace) && !open_quote) {
+        words.push(Word {
+            start: before.len(),
+            text: String::new(),
+            quoted: false,
+        });
+    }
+
+    let (first, rest) = match words.split_first() {
+        Some((first, rest)) if !rest.is_empty() => (first, rest),
+        // Still typing the command itself.  The space saves typing one.
+        _ => {
+            let start = words.first().map_or(before.len(), |w| w.start);
+            let typed = words.first().map_or("", |w| w.text.as_str());
+            let commands = matching(COMMANDS.iter().copied(), typed)
+                .into_iter()
+                .map(|command| format!("{} ", command))
+                .collect();
+            return (start, commands);
+        }
+    };
+
+    // Everything after the last keyword (leaving out the word being typed,
+    // which might be the start of a name with 'to' in it)
+    let done = &rest[..rest.len() - 1];
+    let keyword = done
+        .iter()
+        .rposition(|w| !w.quoted && KEYWORDS.iter().any(|k| w.text.eq_ignore_ascii_case(k)));
+    let (keyword, part) = match keyword {
+        Some(index) => (Some(rest[index].text.to_lowercase()), &rest[index + 1..]),
+        None => (None, rest),
+    };
+    let start = part[0].start;
+    let typed = before[start..].trim_start_matches('"');
+
+    let command = first.text.to_lowercase();
+    let departments = || quote_if_needed(matching(departments.iter().map(String::as_str), typed));
+    let names = || quote_if_needed(matching(names.iter().map(String::as_str), typed));
+    let candidates = match (command.as_str(), keyword.as_deref()) {
+        ("add", Some("to")) | ("move", Some("to")) | (_, Some("from")) => departments(),
+        ("list", None) | ("tree", None) | ("rename", None) => departments(),
+        ("set", None) | ("clear", None) => matching(FIELDS.iter().copied(), typed)
+            .into_iter()
+            .map(|field| format!("{} of ", field))
+            .collect(),
+        // Only a manager is someone.  Titles and dates can be anything.
+        ("set", Some("to")) if rest[0].text.eq_ignore_ascii_case("manager") => names(),
+        ("set", Some("to")) => Vec::new(),
+        (_, Some("of")) => names(),
+        ("remove", None)
+        | ("move", None)
+        | ("find", None)
+        | ("show", None)
+        | ("chain", None)
+        | ("search", None) => names(),
+        _ => Vec::new(),
+    };
+    (start, candidates)
+}
+
+fn split(line: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let quoted = c == '"';
+        if quoted {
+            chars.next();
+        }
+        let mut text = String::new();
+        while let Some(&(_, c)) = chars.peek() {
+            chars.next();
+            if quoted && c == '"' {
+                break;
+            }
+            if !quoted && c.is_whitespace() {
+                break;
+            }
+            text.push(c);
+        }
+        words.push(Word {
+            start,
+            text,
+            quoted,
+        });
+    }
+    words
+}
+
+// The candidates that start with what's been typed, ignoring case
+fn matching<'a>(candidates: impl Iterator<Item = &'a str>, typed: &str) -> Vec<String> {
+    let typed = typed.to_lowercase();
+    candidates
+        .filter(|c| c.to_lowercase().starts_with(&typed))
+        .map(String::from)
+        .collect()
+}
+
+// A name with 'to', 'from' or 'of' in it has to be quoted to be read back
+// as one name
+fn quote_if_needed(candidates: Vec<String>) -> Vec<String> {
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            let has_keyword = candidate
+                .split_whitespace()
+                .any(|word| KEYWORDS.iter().any(|k| word.eq_ignore_ascii_case(k)));
+            if has_keyword {
+                format!(
+                    "\"{}\"",
+                    candidate.replace('\\', "\\\\").replace('"', "\\\"")
+                )
+            } else {
+                candidate
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_from_the_org_chart() {
+        let departments = vec!["Engineering".to_string(), "Human Resources".to_string()];
+        let names = vec![
+            "Mary Ann".to_string(),
+            "Sally".to_string(),
+            "Tom from Accounts".to_string(),
+        ];
+        let complete = |line: &str| complete(line, &departments, &names);
+
+        assert_eq!(
+            (0, vec!["Search ".to_string(), "Set ".to_string()]),
+            complete("se")
+        );
+        assert_eq!((5, vec!["Engineering".to_string()]), complete("List eng"));
+        assert_eq!(
+            (5, vec!["Human Resources".to_string()]),
+            complete("List Human R")
+        );
+        assert_eq!((5, vec!["Mary Ann".to_string()]), complete("Move mary"));
+        assert_eq!(
+            (
+                17,
+                vec!["Engineering".to_string(), "Human Resources".to_string()]
+            ),
+            complete("Move Mary Ann to ")
+        );
+        assert_eq!((4, vec!["title of ".to_string()]), complete("Set t"));
+        assert_eq!(
+            (15, vec!["Sally".to_string()]),
+            complete("Set manager of s")
+        );
+        assert_eq!(
+            (27, vec!["Sally".to_string()]),
+            complete("Set manager of Mary Ann to S")
+        );
+        assert_eq!((25, vec![]), complete("Set title of Mary Ann to "));
+        assert_eq!((25, vec![]), complete("Set start of Mary Ann to S"));
+        assert_eq!(
+            (5, vec!["\"Tom from Accounts\"".to_string()]),
+            complete("Find \"Tom f")
+        );
+        // A new name can be anything
+        assert_eq!((4, vec![]), complete("Add S"));
+    }
+
+    #[test]
+    fn history_goes_next_to_the_org_chart() {
+        assert_eq!(
+            PathBuf::from("emp_list.txt.history"),
+            history_file("emp_list.txt")
+        );
+        assert_eq!(
+            PathBuf::from("x.history.history"),
+            history_file("x.history")
+        );
+    }
+}
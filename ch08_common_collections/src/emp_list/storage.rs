@@ -0,0 +1,460 @@
+// Saving the directory, and its history for Undo and Redo, to a file and
+// reading them back.  The file is plain text so it can be read, and fixed if
+// need be, by hand:
+//
+//     emp_list 3
+//     next_id 4
+//     employee 1
+//     name Sally
+//     department Engineering
+//     title Engineer
+//     start 2021-04-01
+//     manager 3
+//     employee 3
+//     name Joe
+//     department Engineering
+//
+// The first line says which version of the layout the rest of the file uses,
+// so that files written by an older version of the program can still be read.
+// Every other line starts with a word saying what it holds, and the rest of
+// the line is the value, spaces and all.  Each employee starts with their
+// number, and the fields that follow belong to them.
+//
+// The history comes after the employees.  'undone' says how many of the
+// operations at the end have been undone, and each operation lists the
+// employees it changed, as they were before it and after:
+//
+//     undone 0
+//     operation Set title of Sally to Engineer
+//     change 1
+//     before
+//     name Sally
+//     department Engineering
+//     after
+//     name Sally
+//     department Engineering
+//     title Engineer
+//
+// Version 2 was the same without the history.  Version 1 only had names,
+// listed under the department they were in:
+//
+//     emp_list 1
+//     department Engineering
+//     employee Sally
+//
+// Those are numbered in the order they appear when loaded.
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::directory::{Date, Directory, Employee};
+use super::history::{Change, History, Operation};
+
+const VERSION: u32 = 3;
+
+pub fn save(path: &str, directory: &Directory, history: &History) -> io::Result<()> {
+    let mut contents = format!("emp_list {}\n", VERSION);
+    contents.push_str(&format!("next_id {}\n", directory.next_id));
+    for employee in directory.employees() {
+        contents.push_str(&format!("employee {}\n", employee.id));
+        push_fields(&mut contents, employee);
+    }
+    contents.push_str(&format!("undone {}\n", history.undone));
+    for operation in &history.operations {
+        contents.push_str(&format!("operation {}\n", operation.description));
+        for change in &operation.changes {
+            contents.push_str(&format!("change {}\n", change.id));
+            if let Some(before) = &change.before {
+                contents.push_str("before\n");
+                push_fields(&mut contents, before);
+            }
+            if let Some(after) = &change.after {
+                contents.push_str("after\n");
+                push_fields(&mut contents, after);
+            }
+        }
+    }
+    write_atomically(path, contents.as_bytes())
+}
+
+fn push_fields(contents: &mut String, employee: &Employee) {
+    contents.push_str(&format!("name {}\n", employee.name));
+    contents.push_str(&format!("department {}\n", employee.department));
+    if let Some(title) = &employee.title {
+        contents.push_str(&format!("title {}\n", title));
+    }
+    if let Some(start_date) = employee.start_date {
+        contents.push_str(&format!("start {}\n", start_date));
+    }
+    if let Some(manager) = employee.manager {
+        contents.push_str(&format!("manager {}\n", manager));
+    }
+}
+
+// If we wrote straight over the old file and crashed halfway, we'd be left
+// with half an org chart.  Instead the new contents go into a temporary file
+// next to it, which is renamed over the old one once it's safely on disk.  A
+// rename within one directory either happens completely or not at all, so
+// the file always holds either the old org chart or the new one.
+fn write_atomically(path: &str, contents: &[u8]) -> io::Result<()> {
+    let path = Path::new(path);
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = File::create(&temp_path).and_then(|mut temp| {
+        temp.write_all(contents)?;
+        // Make sure it's really on the disk, not just in the OS's cache,
+        // before the rename makes it the real file
+        temp.sync_all()
+    });
+    match result.and_then(|()| fs::rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+// Files from before there was a history load with an empty one
+pub fn load(path: &str) -> io::Result<(Directory, History)> {
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines();
+
+    let version = lines
+        .next()
+        .and_then(|line| line.strip_prefix("emp_list "))
+        .and_then(|version| version.parse::<u32>().ok())
+        .ok_or_else(|| invalid_data(1, "this isn't an emp_list file".to_string()))?;
+    // The version was line 1
+    let lines = lines.enumerate().map(|(index, line)| {
+        let (kind, value) = line.split_once(' ').unwrap_or((line, ""));
+        (index + 2, kind, value)
+    });
+    match version {
+        1 => Ok((load_version_1(lines)?, History::default())),
+        2 | 3 => load_version_3(lines),
+        _ => Err(invalid_data(
+            1,
+            format!("version {} files aren't supported", version),
+        )),
+    }
+}
+
+fn load_version_1<'a>(
+    lines: impl Iterator<Item = (usize, &'a str, &'a str)>,
+) -> io::Result<Directory> {
+    let mut directory = Directory::new();
+    let mut department = None;
+    for (line_number, kind, value) in lines {
+        match kind {
+            "department" => department = Some(value),
+            "employee" => match department {
+                Some(department) => {
+                    directory.add(value, department);
+                }
+                None => {
+                    return Err(invalid_data(
+                        line_number,
+                        "employee before any department".to_string(),
+                    ))
+                }
+            },
+            "" => {}
+            _ => return Err(unknown(line_number, kind)),
+        }
+    }
+    Ok(directory)
+}
+
+// Version 2 is read the same way, since it's version 3 without a history
+fn load_version_3<'a>(
+    lines: impl Iterator<Item = (usize, &'a str, &'a str)>,
+) -> io::Result<(Directory, History)> {
+    let mut directory = Directory::new();
+    let mut history = History::default();
+    // The employee being read, where they go once all their fields are in,
+    // and the line they started on
+    let mut current: Option<(Employee, Block, usize)> = None;
+    for (line_number, kind, value) in lines {
+        let block = match kind {
+            "employee" => Some(Block::Employee(parse_number(line_number, value)?)),
+            "before" | "after" => {
+                let change = history
+                    .operations
+                    .last()
+                    .and_then(|operation| operation.changes.last())
+                    .ok_or_else(|| {
+                        invalid_data(line_number, format!("{} before any change", kind))
+                    })?;
+                match kind {
+                    "before" => Some(Block::Before(change.id)),
+                    _ => Some(Block::After(change.id)),
+                }
+            }
+            _ => None,
+        };
+        if let Some(block) = block {
+            if let Some((employee, block, started)) = current.take() {
+                finish(&mut directory, &mut history, employee, block, started)?;
+            }
+            let id = match block {
+                Block::Employee(id) | Block::Before(id) | Block::After(id) => id,
+            };
+            let employee = Employee {
+                id,
+                name: String::new(),
+                department: String::new(),
+                title: None,
+                start_date: None,
+                manager: None,
+            };
+            current = Some((employee, block, line_number));
+            continue;
+        }
+
+        match kind {
+            "next_id" => directory.next_id = parse_number(line_number, value)?,
+            "undone" => history.undone = parse_number(line_number, value)? as usize,
+            // An operation or change ends whoever was being read, so these
+            // finish them first
+            "operation" | "change" => {
+                if let Some((employee, block, started)) = current.take() {
+                    finish(&mut directory, &mut history, employee, block, started)?;
+                }
+                if kind == "operation" {
+                    history.operations.push(Operation {
+                        description: value.to_string(),
+                        changes: Vec::new(),
+                    });
+                } else {
+                    let operation = history.operations.last_mut().ok_or_else(|| {
+                        invalid_data(line_number, "change before any operation".to_string())
+                    })?;
+                    operation.changes.push(Change {
+                        id: parse_number(line_number, value)?,
+                        before: None,
+                        after: None,
+                    });
+                }
+            }
+            "" => {}
+            _ => {
+                let (employee, _, _) = current.as_mut().ok_or_else(|| {
+                    invalid_data(line_number, format!("{} before any employee", kind))
+                })?;
+                match kind {
+                    "name" => employee.name = value.to_string(),
+                    "department" => employee.department = value.to_string(),
+                    "title" => employee.title = Some(value.to_string()),
+                    "start" => {
+                        employee.start_date =
+                            Some(Date::parse(value).map_err(|e| invalid_data(line_number, e))?)
+                    }
+                    "manager" => employee.manager = Some(parse_number(line_number, value)?),
+                    _ => return Err(unknown(line_number, kind)),
+                }
+            }
+        }
+    }
+    if let Some((employee, block, started)) = current.take() {
+        finish(&mut directory, &mut history, employee, block, started)?;
+    }
+
+    // Checked once everyone is loaded, since a manager can come later in the
+    // file than the people who report to them
+    for employee in directory.employees() {
+        if let Some(manager) = employee.manager {
+            if directory.get(manager).is_none() {
+                return Err(invalid_data(
+                    0,
+                    format!(
+                        "#{} reports to #{}, who doesn't exist",
+                        employee.id, manager
+                    ),
+                ));
+            }
+            if directory.reports_to(manager, employee.id) {
+                return Err(invalid_data(
+                    0,
+                    format!(
+                        "#{} reports to #{}, which goes round in a circle",
+                        employee.id, manager
+                    ),
+                ));
+            }
+        }
+    }
+    if history.undone > history.operations.len() {
+        return Err(invalid_data(
+            0,
+            format!(
+                "{} operations are undone, but there are only {}",
+                history.undone,
+                history.operations.len()
+            ),
+        ));
+    }
+    Ok((directory, history))
+}
+
+// What the employee being read is: someone in the directory, or how someone
+// was before or after an operation in the history
+enum Block {
+    Employee(u32),
+    Before(u32),
+    After(u32),
+}
+
+fn parse_number(line_number: usize, value: &str) -> io::Result<u32> {
+    value
+        .parse()
+        .map_err(|_| invalid_data(line_number, format!("'{}' isn't a number", value)))
+}
+
+// Puts an employee where they belong once all of their fields have been read
+fn finish(
+    directory: &mut Directory,
+    history: &mut History,
+    employee: Employee,
+    block: Block,
+    line_number: usize,
+) -> io::Result<()> {
+    if employee.name.is_empty() || employee.department.is_empty() {
+        return Err(invalid_data(
+            line_number,
+            format!("#{} needs a name and a department", employee.id),
+        ));
+    }
+    // The change is always the last one, since an employee's fields end at
+    // the next change
+    if let Block::Before(_) | Block::After(_) = block {
+        let change = history
+            .operations
+            .last_mut()
+            .and_then(|operation| operation.changes.last_mut())
+            .unwrap();
+        match block {
+            Block::Before(_) => change.before = Some(employee),
+            _ => change.after = Some(employee),
+        }
+        return Ok(());
+    }
+    if directory.employees.contains_key(&employee.id) {
+        return Err(invalid_data(
+            line_number,
+            format!("#{} is in the file twice", employee.id),
+        ));
+    }
+    // A hand-edited file might not have next_id right.  It also might have a
+    // number so big there's no next one to give out.
+    let next_id = employee.id.checked_add(1).ok_or_else(|| {
+        invalid_data(line_number, format!("#{} is too big a number", employee.id))
+    })?;
+    directory.next_id = directory.next_id.max(next_id);
+    directory.employees.insert(employee.id, employee);
+    Ok(())
+}
+
+fn unknown(line_number: usize, kind: &str) -> io::Error {
+    invalid_data(line_number, format!("unknown entry '{}'", kind))
+}
+
+// Line 0 is for problems with the file as a whole
+fn invalid_data(line_number: usize, message: String) -> io::Error {
+    let message = match line_number {
+        0 => message,
+        _ => format!("line {}: {}", line_number, message),
+    };
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_loads() {
+        let dir = std::env::temp_dir().join(format!("emp_list_storage_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("org.txt");
+        let path = path.to_str().unwrap();
+
+        let mut directory = Directory::new();
+        let mary_ann = directory.add("Mary Ann", "Human Resources");
+        let toby = directory.add("Toby", "Human Resources");
+        directory.get_mut(toby).unwrap().title = Some("HR Rep".to_string());
+        directory.get_mut(toby).unwrap().start_date = Date::parse("2005-03-24").ok();
+        directory.set_manager(toby, Some(mary_ann)).unwrap();
+        let mut history = History::default();
+        let before = directory.clone();
+        directory.remove(mary_ann);
+        history.record(Operation::between("Remove Mary Ann", &before, &directory));
+        history.undo(&mut directory).unwrap();
+        save(path, &directory, &history).unwrap();
+        assert_eq!(
+            "emp_list 3\nnext_id 3\n\
+             employee 1\nname Mary Ann\ndepartment Human Resources\n\
+             employee 2\nname Toby\ndepartment Human Resources\ntitle HR Rep\n\
+             start 2005-03-24\nmanager 1\n\
+             undone 1\noperation Remove Mary Ann\n\
+             change 1\nbefore\nname Mary Ann\ndepartment Human Resources\n\
+             change 2\nbefore\nname Toby\ndepartment Human Resources\ntitle HR Rep\n\
+             start 2005-03-24\nmanager 1\n\
+             after\nname Toby\ndepartment Human Resources\ntitle HR Rep\n\
+             start 2005-03-24\n",
+            fs::read_to_string(path).unwrap()
+        );
+        assert_eq!((directory, history), load(path).unwrap());
+        // Nothing is left behind from writing it
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+
+        fs::write(path, "emp_list 2\nname Amir\n").unwrap();
+        assert_eq!(
+            "line 2: name before any employee",
+            load(path).unwrap_err().to_string()
+        );
+        fs::write(
+            path,
+            "emp_list 2\nemployee 1\nname A\ndepartment D\nmanager 2\n\
+             employee 2\nname B\ndepartment D\nmanager 1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "#1 reports to #2, which goes round in a circle",
+            load(path).unwrap_err().to_string()
+        );
+        fs::write(
+            path,
+            "emp_list 3\nemployee 4294967295\nname A\ndepartment D\n",
+        )
+        .unwrap();
+        assert!(load(path)
+            .unwrap_err()
+            .to_string()
+            .contains("#4294967295 is too big a number"));
+        fs::write(path, "emp_list 7\n").unwrap();
+        assert!(load(path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn upgrades_version_1_files() {
+        let dir = std::env::temp_dir().join(format!("emp_list_upgrade_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("org.txt");
+        let path = path.to_str().unwrap();
+
+        fs::write(
+            path,
+            "emp_list 1\ndepartment Sales\nemployee Amir\nemployee Sue\n",
+        )
+        .unwrap();
+        let (directory, history) = load(path).unwrap();
+        assert_eq!(Ok(2), directory.resolve("Sue"));
+        assert!(history.operations.is_empty());
+        assert_eq!("Sales", directory.get(1).unwrap().department);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+// The employee list project lives here, in a library, so that it can be
+// tested and used from other programs.  main.rs is the chapter's tour of the
+// collections, which runs it at the end.
+pub mod emp_list;
@@ -1,4 +1,17 @@
+use std::collections::HashMap;
+
+use common_collections::emp_list;
+
 fn main() {
+    // 'cargo run -- --batch' runs only the employee list, reading commands
+    // for it from a file or stdin, so scripts don't have to wade through
+    // everything else this prints.  Without --batch, any other arguments are
+    // left alone and everything runs as usual.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--batch") {
+        std::process::exit(emp_list::batch(&args));
+    }
+
     // This covers the three most commonly used data structures that are
     // collections.  Most data types can only represent one specific value, but
     // collections can contain multiple values.
@@ -306,7 +319,7 @@ fn projects() {
     // a department ("Add Sally to Engineering", "Add Amir to Sales").  Then let
     // user retrieve a list of all people in a department or all people in the
     // company by department, sorted alphabetically
-    emp_list();
+    emp_list::emp_list();
 }
 
 fn calc_mean(my_vec: &Vec<i32>) -> f32 {
@@ -372,87 +385,3 @@ fn prettier_pig_latin(word: &String) -> String {
         _ => format!("{}{}ay", word[1..].to_string(), first_letter),
     }
 }
-
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-// BufRead is used to allow the for loop (.lines())
-use itertools::Itertools;
-use std::io::{self, BufRead};
-fn emp_list() {
-    // "Add Sally to Engineering";
-    // "Add Joe to Engineering";
-    // "Add Bob to Engineering";
-    // "Add Chris to Analytics";
-    // "Add Sue to Analytics";
-    // "Add Amir to Sales";
-
-    let mut org_chart: HashMap<String, Vec<String>> = HashMap::new();
-
-    println!("Type 'Add <name> to <department>' to add an employee:");
-    println!("Type 'List <department>' to list the employees of a department");
-    println!("Type 'All' to list all employees by department");
-    println!("Type 'Quit' to quit");
-    for line in io::stdin().lock().lines() {
-        let input = line.expect("Error: Unable to read user input");
-        match Command::parse_line(&input) {
-            Command::Add { name, department } => match org_chart.entry(department.to_string()) {
-                Entry::Vacant(department) => {
-                    department.insert(vec![name]);
-                }
-                Entry::Occupied(mut department) => {
-                    department.get_mut().push(name);
-                }
-            },
-            Command::List { department } => match org_chart.get(&department) {
-                Some(names) => {
-                    let mut names = names.clone();
-                    names.sort();
-                    for name in names {
-                        println!("{}: {}", department, name);
-                    }
-                }
-                None => {
-                    println!("No one works in that department");
-                }
-            },
-            Command::All => {
-                println!("{:?}", org_chart.keys());
-                for department in org_chart.keys().sorted() {
-                    let mut names = org_chart.get(department).unwrap().clone();
-                    names.sort();
-                    for name in names {
-                        println!("{}: {:?}", department, name);
-                    }
-                }
-            }
-            Command::Quit => break,
-            Command::InputError => println!("Input error.  Please try again"),
-        }
-    }
-}
-
-enum Command {
-    Add { name: String, department: String },
-    List { department: String },
-    All,
-    Quit,
-    InputError,
-}
-
-impl Command {
-    fn parse_line(line: &str) -> Command {
-        let words: Vec<&str> = line.trim().split_whitespace().collect();
-        match words.as_slice() {
-            ["Add", name, "to", department] => Command::Add {
-                name: name.to_string(),
-                department: department.to_string(),
-            },
-            ["List", department] => Command::List {
-                department: department.to_string(),
-            },
-            ["All"] => Command::All,
-            ["Quit"] => Command::Quit,
-            _ => Command::InputError,
-        }
-    }
-}
@@ -0,0 +1,32 @@
+// Shared by the integration tests.  Each test binary only uses some of it.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+
+use common_collections::emp_list::Directory;
+
+// The org chart from the chapter, plus a second Sam to make names ambiguous
+pub fn sample_directory() -> Directory {
+    let mut directory = Directory::new();
+    for (name, department) in [
+        ("Sally", "Engineering"),
+        ("Joe", "Engineering"),
+        ("Bob", "Engineering"),
+        ("Chris", "Analytics"),
+        ("Sue", "Analytics"),
+        ("Amir", "Sales"),
+        ("Sam", "Sales"),
+        ("Sam", "Engineering"),
+    ] {
+        directory.add(name, department);
+    }
+    directory
+}
+
+// A directory of its own for each test, so they can run at the same time
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("emp_list_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
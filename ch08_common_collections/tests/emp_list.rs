@@ -0,0 +1,163 @@
+// Uses the employee list as a library: the Directory directly, Commands
+// parsed on their own, and a Session running them against a file.
+use std::fs;
+
+use common_collections::emp_list::{Command, Directory, Employee, Session};
+
+mod common;
+
+use common::{sample_directory, temp_dir};
+
+fn names(employees: &[&Employee]) -> Vec<String> {
+    employees
+        .iter()
+        .map(|e| format!("{} #{}", e.name, e.id))
+        .collect()
+}
+
+#[test]
+fn lists_are_sorted() {
+    let directory = sample_directory();
+    assert_eq!(
+        vec!["Analytics", "Engineering", "Sales"],
+        directory.departments()
+    );
+    assert_eq!(
+        vec!["Bob #3", "Joe #2", "Sally #1", "Sam #8"],
+        names(&directory.list("Engineering"))
+    );
+    assert_eq!(
+        vec!["Chris #4", "Sue #5", "Bob #3", "Joe #2", "Sally #1", "Sam #8", "Amir #6", "Sam #7"],
+        names(&directory.all())
+    );
+    assert!(directory.list("Marketing").is_empty());
+}
+
+#[test]
+fn adding_and_removing() {
+    let mut directory = sample_directory();
+    let sam = directory.resolve("#7").unwrap();
+    assert_eq!("Sam", directory.remove(sam).unwrap().name);
+    // The other Sam is the only one now
+    assert_eq!(Ok(8), directory.resolve("Sam"));
+    assert_eq!(None, directory.remove(sam));
+    // Numbers aren't reused
+    assert_eq!(9, directory.add("Sam", "Sales"));
+}
+
+#[test]
+fn directory_errors() {
+    let mut directory = sample_directory();
+    assert_eq!(
+        Err("No one called Zed works here".to_string()),
+        directory.resolve("Zed")
+    );
+    assert!(directory
+        .resolve("Sam")
+        .unwrap_err()
+        .contains("2 people called Sam"));
+    assert_eq!(
+        Err("There's already a department called Sales".to_string()),
+        directory.rename_department("Analytics", "Sales")
+    );
+    assert_eq!(
+        Err("There's no department called Marketing".to_string()),
+        directory.rename_department("Marketing", "Ads")
+    );
+    assert_eq!(
+        Err("No one can be their own manager".to_string()),
+        directory.set_manager(1, Some(1))
+    );
+}
+
+#[test]
+fn commands_parse_without_running() {
+    assert_eq!(
+        Ok(Command::List {
+            department: "Human Resources".to_string()
+        }),
+        Command::parse_line("LIST Human Resources")
+    );
+    assert_eq!(Ok(Command::Undo), Command::parse_line("undo"));
+    assert!(Command::parse_line("").is_err());
+    assert!(Command::parse_line("Add Sally").is_err());
+}
+
+#[test]
+fn sessions_run_commands_and_save() {
+    let dir = temp_dir("session");
+    let file = dir.join("org.txt").to_str().unwrap().to_string();
+
+    let (mut session, problem) = Session::open(file.clone());
+    assert_eq!(None, problem);
+    assert_eq!(
+        Ok(Some("Added Sally to Engineering as #1\n".to_string())),
+        session.run("Add Sally to Engineering")
+    );
+    assert!(session.run("Add Joe to Engineering").is_ok());
+    assert!(session.run("Set manager of Sally to Joe").is_ok());
+    assert_eq!(
+        Err("No one works in Sales".to_string()),
+        session.run("List Sales")
+    );
+    assert_eq!(
+        Err("No one works in engineerin.  Did you mean Engineering?".to_string()),
+        session.run("List engineerin")
+    );
+    assert_eq!(
+        Err("Joe is already in Engineering".to_string()),
+        session.run("Move Joe to Engineering")
+    );
+    // Only the commands that worked can be undone
+    assert_eq!(3, session.history.operations.len());
+    assert_eq!(
+        Ok(Some("Undid Set manager of Sally to Joe\n".to_string())),
+        session.run("Undo")
+    );
+    assert_eq!(None, session.directory.get(1).unwrap().manager);
+    session.json = true;
+    assert_eq!(
+        Ok(Some(
+            "[{\"id\":2,\"name\":\"Joe\",\"department\":\"Engineering\",\
+             \"title\":null,\"start\":null,\"manager\":null}]\n"
+                .to_string()
+        )),
+        session.run("Search jo")
+    );
+    assert_eq!(Ok(None), session.run("Quit"));
+    session.save().unwrap();
+
+    let (reopened, problem) = Session::open(file.clone());
+    assert_eq!(None, problem);
+    assert_eq!(session.directory, reopened.directory);
+    assert_eq!(session.history, reopened.history);
+
+    fs::write(&file, "not an org chart\n").unwrap();
+    let (empty, problem) = Session::open(file);
+    assert!(problem.unwrap().contains("this isn't an emp_list file"));
+    assert_eq!(Directory::new(), empty.directory);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn files_that_wont_load_are_left_alone() {
+    let dir = temp_dir("unloadable");
+    let file = dir.join("org.txt").to_str().unwrap().to_string();
+    let contents = b"emp_list 99\nsomething from a newer version\n";
+    fs::write(&file, contents).unwrap();
+
+    let (mut session, problem) = Session::open(file.clone());
+    assert!(problem.is_some());
+    assert!(session.run("Add Sally to Engineering").is_ok());
+    assert_eq!(Ok(None), session.run("Quit"));
+    session.save_on_exit().unwrap();
+    assert_eq!(contents.to_vec(), fs::read(&file).unwrap());
+
+    // Saving on purpose still works
+    assert!(session.run("Save").is_ok());
+    session.save_on_exit().unwrap();
+    let (reopened, problem) = Session::open(file);
+    assert_eq!(None, problem);
+    assert_eq!(session.directory, reopened.directory);
+    fs::remove_dir_all(&dir).unwrap();
+}
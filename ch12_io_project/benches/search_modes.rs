@@ -0,0 +1,96 @@
+// Times every search mode against made-up files of a few different shapes.
+// Run with 'cargo bench'.  Each mode is run over and over until enough time
+// has passed to give a steady number, and the average time per search is
+// printed along with how many megabytes per second that works out to.
+extern crate io_project;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use io_project::fields::{self, Field};
+use io_project::{search, search_case_insensitive, search_lines, search_multiline, Config};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{corpus, Rng};
+
+// (number of lines, characters per line)
+const CORPORA: [(usize, usize); 5] = [
+    (1_000, 40),
+    (10_000, 40),
+    (100_000, 40),
+    (10_000, 400),
+    (1_000, 4_000),
+];
+
+const MIN_TIME: Duration = Duration::from_millis(300);
+
+fn main() {
+    let mut rng = Rng::new(42);
+    println!(
+        "{:<16} {:<28} {:>12} {:>10}",
+        "corpus", "mode", "time/search", "MB/s"
+    );
+
+    for (lines, line_len) in CORPORA {
+        let contents = corpus(&mut rng, lines, line_len);
+        let name = format!("{} x {}", lines, line_len);
+
+        let mut sensitive = config("nobody");
+        sensitive.case_sensitive = true;
+        let mut insensitive = config("NoBody");
+        insensitive.case_sensitive = false;
+        let mut spanning = config("frog\nbog");
+        spanning.case_sensitive = true;
+
+        bench(&name, "search", &contents, || {
+            search("nobody", &contents).len()
+        });
+        bench(&name, "search_case_insensitive", &contents, || {
+            search_case_insensitive("NoBody", &contents).len()
+        });
+        bench(&name, "search_lines", &contents, || {
+            search_lines(&sensitive, &contents).len()
+        });
+        bench(&name, "search_lines (insensitive)", &contents, || {
+            search_lines(&insensitive, &contents).len()
+        });
+        bench(&name, "search_multiline", &contents, || {
+            search_multiline(&spanning, &contents).len()
+        });
+        bench(&name, "search_multiline (insens.)", &contents, || {
+            search_multiline(&insensitive, &contents).len()
+        });
+        bench(&name, "search_fields", &contents, || {
+            fields::search_fields(&sensitive, &Field::Index(2), ',', &contents)
+                .unwrap()
+                .len()
+        });
+    }
+}
+
+fn config(query: &str) -> Config {
+    let args: Vec<String> = ["io_project", query, "corpus.txt"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    Config::new(&args).unwrap()
+}
+
+// black_box stops the compiler from noticing we never use the results and
+// skipping the search altogether
+fn bench<F: FnMut() -> usize>(corpus: &str, mode: &str, contents: &str, mut f: F) {
+    let started = Instant::now();
+    let mut runs = 0;
+    while started.elapsed() < MIN_TIME {
+        black_box(f());
+        runs += 1;
+    }
+    let per_run = started.elapsed() / runs;
+    let megabytes_per_second = contents.len() as f64 / per_run.as_secs_f64() / 1_000_000.0;
+    println!(
+        "{:<16} {:<28} {:>12.2?} {:>10.1}",
+        corpus, mode, per_run, megabytes_per_second
+    );
+}
@@ -0,0 +1,159 @@
+// Opening up tar and zip archives so the files inside them can be searched as
+// if they were sitting on disk.  Reading the archive formats themselves is
+// left to the 'tar', 'zip' and 'flate2' crates.
+use std::io::{self, Cursor, Read};
+
+use flate2::read::GzDecoder;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Tar,
+    // A tarball squeezed with gzip, as in .tar.gz or .tgz
+    TarGz,
+    Zip,
+}
+
+impl Kind {
+    // We go by the name rather than peeking at the contents, the same way
+    // '--field' picks a delimiter from the extension
+    pub fn from_name(name: &str) -> Option<Kind> {
+        let name = name.to_lowercase();
+        if name.ends_with(".tar") {
+            Some(Kind::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Kind::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(Kind::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+// How big a file inside an archive can be when --max-filesize isn't given.
+// A few kilobytes of compressed zeros can unpack to gigabytes, so without a
+// limit a small archive could use up all of the memory.
+pub const DEFAULT_MEMBER_LIMIT: u64 = 256 * 1024 * 1024;
+
+// A file from inside an archive.  'path' is where it sits within the archive,
+// and 'data' is None if the file was bigger than the limit and wasn't read.
+pub struct Member {
+    pub path: String,
+    pub data: Option<Vec<u8>>,
+}
+
+// Reads every regular file out of the archive.  Directories, links and the
+// like are left out since there's nothing in them to search.  No more than
+// 'limit' bytes are unpacked from any one file.
+pub fn members(kind: Kind, bytes: &[u8], limit: u64) -> io::Result<Vec<Member>> {
+    match kind {
+        Kind::Tar => tar_members(bytes, limit),
+        Kind::TarGz => tar_members(GzDecoder::new(bytes), limit),
+        Kind::Zip => zip_members(bytes, limit),
+    }
+}
+
+// The size an archive says a file is can't be trusted, so even when it says
+// the file is small enough we stop reading one byte past the limit
+fn read_member(reader: impl Read, stated_size: u64, limit: u64) -> io::Result<Option<Vec<u8>>> {
+    if stated_size > limit {
+        return Ok(None);
+    }
+    let mut data = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        Ok(None)
+    } else {
+        Ok(Some(data))
+    }
+}
+
+fn tar_members<R: Read>(reader: R, limit: u64) -> io::Result<Vec<Member>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut members = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let stated_size = entry.size();
+        let data = read_member(&mut entry, stated_size, limit)?;
+        members.push(Member { path, data });
+    }
+    Ok(members)
+}
+
+fn zip_members(bytes: &[u8], limit: u64) -> io::Result<Vec<Member>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let mut members = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = file.name().to_string();
+        let stated_size = file.size();
+        let data = read_member(&mut file, stated_size, limit)?;
+        members.push(Member { path, data });
+    }
+    Ok(members)
+}
+
+// Archives tend to be full of things that aren't text (images, compiled
+// libraries and so on).  Text files almost never contain a zero byte, so like
+// grep we take one in the first few kilobytes to mean the file is binary.
+// UTF-16 text is full of zero bytes, but it normally starts with a BOM.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let (_, bom_len) = crate::encoding::Encoding::detect(bytes);
+    bom_len == 0 && bytes.iter().take(8192).any(|&b| b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // Reading tar and tar.gz files is tested in tests/archives.rs, where the
+    // helper for building them is
+
+    #[test]
+    fn kinds_from_names() {
+        assert_eq!(Some(Kind::Tar), Kind::from_name("build.tar"));
+        assert_eq!(Some(Kind::TarGz), Kind::from_name("build.TAR.GZ"));
+        assert_eq!(Some(Kind::Zip), Kind::from_name("outer.zip:inner.zip"));
+        assert_eq!(None, Kind::from_name("poem.txt"));
+    }
+
+    #[test]
+    fn reads_zip_members() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        writer.add_directory("docs/", options).unwrap();
+        writer.start_file("docs/poem.txt", options).unwrap();
+        writer.write_all(b"Who are you?").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let members = members(Kind::Zip, &bytes, 100).unwrap();
+        assert_eq!(1, members.len());
+        assert_eq!("docs/poem.txt", members[0].path);
+        assert_eq!(Some(b"Who are you?".to_vec()), members[0].data);
+    }
+
+    #[test]
+    fn stops_reading_at_the_limit() {
+        // Even when the archive claims a file is smaller than it is
+        let lying = Cursor::new(vec![1; 5000]);
+        assert_eq!(None, read_member(lying, 10, 1000).unwrap());
+        let honest = Cursor::new(vec![1; 5]);
+        assert_eq!(Some(vec![1; 5]), read_member(honest, 5, 1000).unwrap());
+    }
+
+    #[test]
+    fn binary_detection() {
+        assert!(looks_binary(b"\x7fELF\x00\x01"));
+        assert!(!looks_binary(b"plain text"));
+        assert!(!looks_binary(&[0xFF, 0xFE, b'h', 0x00]));
+    }
+}
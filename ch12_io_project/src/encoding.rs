@@ -0,0 +1,174 @@
+// Rust strings are always UTF-8, which is why 'fs::read_to_string' returns an
+// error as soon as it sees a file saved as UTF-16.  To search those files we
+// read the raw bytes and turn them into a String ourselves.
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Encoding {
+    // The names accepted by '--encoding'.  Matching is case-insensitive, so
+    // 'UTF-16LE' and 'utf-16le' both work
+    pub fn from_label(label: &str) -> Option<Encoding> {
+        match label.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    // A byte order mark (BOM) is a marker some editors put at the very start of
+    // a file to say how it's encoded.  This returns the encoding the BOM
+    // stands for and how many bytes it takes up.  Latin-1 has no BOM, so
+    // without one we fall back to UTF-8.
+    pub fn detect(bytes: &[u8]) -> (Encoding, usize) {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => (Encoding::Utf8, 3),
+            [0xFF, 0xFE, ..] => (Encoding::Utf16Le, 2),
+            [0xFE, 0xFF, ..] => (Encoding::Utf16Be, 2),
+            _ => (Encoding::Utf8, 0),
+        }
+    }
+
+    // How many bytes 'text' took up in the original file.  This is what lets
+    // us report byte offsets in the file on disk rather than in our UTF-8 copy
+    pub fn encoded_len(self, text: &str) -> usize {
+        match self {
+            Encoding::Utf8 => text.len(),
+            Encoding::Utf16Le | Encoding::Utf16Be => text.encode_utf16().count() * 2,
+            // Every Latin-1 character is exactly one byte
+            Encoding::Latin1 => text.chars().count(),
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> io::Result<String> {
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec())
+                .map_err(|_| invalid_data("stream did not contain valid UTF-8")),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(invalid_data("UTF-16 stream has an odd number of bytes"));
+                }
+                let units = bytes.chunks(2).map(|pair| match self {
+                    Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                });
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_err(|_| invalid_data("stream did not contain valid UTF-16"))
+            }
+            // Latin-1 lines up with the first 256 Unicode code points, so each
+            // byte converts straight into a char
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// The text of a file after it has been transcoded to UTF-8, along with what we
+// need to know to map positions back onto the original bytes
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    pub bom_len: usize,
+}
+
+// If 'encoding' is None we go by the BOM.  If the user asked for a specific
+// encoding we use that, but still skip over a BOM when it agrees with them.
+pub fn decode(bytes: &[u8], encoding: Option<Encoding>) -> io::Result<Decoded> {
+    let (detected, bom_len) = Encoding::detect(bytes);
+    let encoding = encoding.unwrap_or(detected);
+    let bom_len = if encoding == detected { bom_len } else { 0 };
+    let text = encoding.decode(&bytes[bom_len..])?;
+    Ok(Decoded {
+        text,
+        encoding,
+        bom_len,
+    })
+}
+
+impl Decoded {
+    // Keeps track of where we got to so that mapping offsets in order only
+    // walks over the text once, rather than once per match
+    pub fn offsets(&self) -> OffsetMapper<'_> {
+        OffsetMapper {
+            decoded: self,
+            offset: 0,
+            original: self.bom_len,
+        }
+    }
+}
+
+pub struct OffsetMapper<'a> {
+    decoded: &'a Decoded,
+    offset: usize,
+    original: usize,
+}
+
+impl<'a> OffsetMapper<'a> {
+    // Turns a byte offset into 'Decoded.text' into a byte offset into the file
+    pub fn original(&mut self, offset: usize) -> usize {
+        if offset < self.offset {
+            self.offset = 0;
+            self.original = self.decoded.bom_len;
+        }
+        let skipped = &self.decoded.text[self.offset..offset];
+        self.original += self.decoded.encoding.encoded_len(skipped);
+        self.offset = offset;
+        self.original
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn detects_utf16_bom() {
+        let decoded = decode(&utf16le("Rust:\r\nTrust me."), None).unwrap();
+        assert_eq!(Encoding::Utf16Le, decoded.encoding);
+        assert_eq!("Rust:\r\nTrust me.", decoded.text);
+    }
+
+    #[test]
+    fn explicit_encoding() {
+        let bytes = [0x00, b'h', 0x00, b'i'];
+        let decoded = decode(&bytes, Some(Encoding::Utf16Be)).unwrap();
+        assert_eq!("hi", decoded.text);
+
+        let decoded = decode(&[b'f', 0xFC, b'r'], Some(Encoding::Latin1)).unwrap();
+        assert_eq!("für", decoded.text);
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error() {
+        assert!(decode(&[b'f', 0xFC, b'r'], None).is_err());
+    }
+
+    #[test]
+    fn offsets_in_original_file() {
+        let decoded = decode(&utf16le("ab\ncd"), None).unwrap();
+        let mut offsets = decoded.offsets();
+        // Two bytes of BOM, then two bytes for each of 'a', 'b' and '\n'
+        assert_eq!(2, offsets.original(0));
+        assert_eq!(8, offsets.original(3));
+    }
+}
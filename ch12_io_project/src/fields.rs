@@ -0,0 +1,296 @@
+// Searching delimited text (CSV, TSV and friends) one field at a time.  A
+// plain line search can't tell which column a value is in, so here we split
+// each record into its fields and only test the one that was asked for.
+use std::ops::Range;
+
+use crate::{contains_ignoring_case, match_ranges, Config, Match};
+
+#[derive(Debug, PartialEq)]
+pub enum Field {
+    // Counting from 1, the way spreadsheets and 'cut -f' do
+    Index(usize),
+    // Looked up in the header, which is the first record of the file
+    Name(String),
+}
+
+impl Field {
+    pub fn parse(value: &str) -> Result<Field, &'static str> {
+        match value.parse::<usize>() {
+            Ok(0) => Err("--field numbers start at 1"),
+            Ok(index) => Ok(Field::Index(index)),
+            Err(_) => Ok(Field::Name(value.to_string())),
+        }
+    }
+}
+
+// Turns the value given to '--delimiter' into a character.  Tabs are awkward
+// to type on the command line, so '\t' and 'tab' are accepted too
+pub fn parse_delimiter(value: &str) -> Result<char, &'static str> {
+    let mut chars = value.chars();
+    match (value, chars.next(), chars.next()) {
+        ("\\t", _, _) | ("tab", _, _) => Ok('\t'),
+        (_, Some(c), None) if c != '"' && c != '\n' => Ok(c),
+        _ => Err("--delimiter must be a single character"),
+    }
+}
+
+// Without a '--delimiter', .tsv files are split on tabs and everything else on
+// commas
+pub fn default_delimiter(filename: &str) -> char {
+    if filename.to_lowercase().ends_with(".tsv") {
+        '\t'
+    } else {
+        ','
+    }
+}
+
+// One record of a delimited file.  Usually that's a single line, but a quoted
+// field can contain newlines, so a record may run over several
+pub struct Record<'a> {
+    pub line_number: usize,
+    pub end_line_number: usize,
+    pub offset: usize,
+    pub text: &'a str,
+    pub fields: Vec<String>,
+    // Where each field sits in text, quotes and all
+    pub spans: Vec<Range<usize>>,
+}
+
+// Splits contents into records following the usual CSV rules: a field wrapped
+// in double quotes can contain the delimiter and newlines, and a doubled quote
+// ("") inside a quoted field stands for a single quote.  Blank lines are
+// skipped.
+pub fn records(contents: &str, delimiter: char) -> Vec<Record<'_>> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut spans = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    let mut field_start = 0;
+    let mut line_number = 1;
+    let mut start_line_number = 1;
+
+    let mut chars = contents.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if let Some((_, '"')) = chars.peek() {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            '\n' if in_quotes => {
+                field.push(c);
+                line_number += 1;
+            }
+            _ if in_quotes => field.push(c),
+            '\r' if matches!(chars.peek(), Some((_, '\n'))) => {}
+            '\n' => {
+                fields.push(field);
+                let end = if contents[..index].ends_with('\r') {
+                    index - 1
+                } else {
+                    index
+                };
+                spans.push(field_start..end.max(field_start));
+                push_record(
+                    &mut records,
+                    contents,
+                    start..index,
+                    start_line_number,
+                    line_number,
+                    fields,
+                    spans,
+                );
+                fields = Vec::new();
+                field = String::new();
+                spans = Vec::new();
+                start = index + 1;
+                field_start = start;
+                line_number += 1;
+                start_line_number = line_number;
+            }
+            _ if c == delimiter => {
+                fields.push(std::mem::take(&mut field));
+                spans.push(field_start..index);
+                field_start = index + c.len_utf8();
+            }
+            _ => field.push(c),
+        }
+    }
+    // A file can end with a bare '\r', left over from a Windows line ending
+    // that lost its '\n'.  It isn't part of the last field, any more than
+    // the '\r' of a '\r\n' is.
+    let end = if !in_quotes && field.ends_with('\r') {
+        field.pop();
+        contents.len() - 1
+    } else {
+        contents.len()
+    };
+    fields.push(field);
+    spans.push(field_start..end.max(field_start));
+    push_record(
+        &mut records,
+        contents,
+        start..contents.len(),
+        start_line_number,
+        line_number,
+        fields,
+        spans,
+    );
+    records
+}
+
+fn push_record<'a>(
+    records: &mut Vec<Record<'a>>,
+    contents: &'a str,
+    range: Range<usize>,
+    line_number: usize,
+    end_line_number: usize,
+    fields: Vec<String>,
+    spans: Vec<Range<usize>>,
+) {
+    let text = contents[range.clone()].trim_end_matches('\r');
+    if !text.is_empty() {
+        records.push(Record {
+            line_number,
+            end_line_number,
+            offset: range.start,
+            text,
+            fields,
+            spans: spans
+                .into_iter()
+                .map(|span| span.start - range.start..span.end - range.start)
+                .collect(),
+        });
+    }
+}
+
+// Like search_lines, but the query only has to appear in the chosen field.
+// Records without that field (a short row, say) never match.
+pub fn search_fields<'a>(
+    config: &Config,
+    field: &Field,
+    delimiter: char,
+    contents: &'a str,
+) -> Result<Vec<Match<'a>>, String> {
+    let mut records = records(contents, delimiter).into_iter();
+
+    let index = match field {
+        Field::Index(index) => index - 1,
+        Field::Name(name) => {
+            // The header is only there to name the columns, so it isn't
+            // searched
+            let header = records
+                .next()
+                .map(|record| record.fields)
+                .unwrap_or_default();
+            header
+                .iter()
+                .position(|column| column == name)
+                .ok_or_else(|| format!("no column named '{}' in the header", name))?
+        }
+    };
+
+    let mut results = Vec::new();
+    for record in records {
+        let is_match = match record.fields.get(index) {
+            Some(value) if config.case_sensitive => value.contains(&config.query),
+            Some(value) => contains_ignoring_case(value, &config.query),
+            None => false,
+        };
+        if is_match {
+            // Only the query's appearances inside the chosen field count
+            let span = record.spans[index].clone();
+            let ranges = match_ranges(
+                &config.query,
+                &record.text[span.clone()],
+                config.case_sensitive,
+            )
+            .into_iter()
+            .map(|range| range.start + span.start..range.end + span.start)
+            .collect();
+            results.push(Match {
+                line_number: record.line_number,
+                end_line_number: record.end_line_number,
+                offset: record.offset,
+                line: record.text,
+                ranges,
+            });
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields() {
+        let contents = "\
+name,quote
+Emily,\"Hope is the thing, with feathers\"
+\"Dickinson, E.\",\"She said \"\"nobody\"\"\"\r
+Poem,\"two
+lines\"
+";
+        let records = records(contents, ',');
+        assert_eq!(4, records.len());
+        assert_eq!(
+            vec!["Emily", "Hope is the thing, with feathers"],
+            records[1].fields
+        );
+        assert_eq!(
+            vec!["Dickinson, E.", "She said \"nobody\""],
+            records[2].fields
+        );
+        assert_eq!(vec!["Poem", "two\nlines"], records[3].fields);
+        assert_eq!(4, records[3].line_number);
+        assert_eq!(5, records[3].end_line_number);
+        assert_eq!("Poem,\"two\nlines\"", records[3].text);
+        assert_eq!(vec![0..15, 16..37], records[2].spans);
+    }
+
+    #[test]
+    fn only_searches_the_chosen_field() {
+        let config = Config::new(&["io_project", "Sales", "org.csv"].map(String::from)).unwrap();
+        let contents = "\
+name,department
+Sales,Engineering
+Amir,Sales
+";
+
+        let by_index = search_fields(&config, &Field::Index(2), ',', contents).unwrap();
+        assert_eq!(1, by_index.len());
+        assert_eq!("Amir,Sales", by_index[0].line);
+        assert_eq!(vec![5..10], by_index[0].ranges);
+        assert_eq!(3, by_index[0].line_number);
+
+        let by_name =
+            search_fields(&config, &Field::Name("name".to_string()), ',', contents).unwrap();
+        assert_eq!(1, by_name.len());
+        assert_eq!("Sales,Engineering", by_name[0].line);
+
+        assert!(search_fields(&config, &Field::Name("title".to_string()), ',', contents).is_err());
+
+        // The last line of a Windows file that's lost its final '\n'
+        let contents = "name,department\r\nAmir,Sales\r";
+        let results = search_fields(&config, &Field::Index(2), ',', contents).unwrap();
+        assert_eq!("Amir,Sales", results[0].line);
+        assert_eq!(vec![5..10], results[0].ranges);
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(Ok('\t'), parse_delimiter("\\t"));
+        assert_eq!(Ok(';'), parse_delimiter(";"));
+        assert!(parse_delimiter(",,").is_err());
+        assert_eq!(Ok(Field::Index(3)), Field::parse("3"));
+        assert!(Field::parse("0").is_err());
+    }
+}
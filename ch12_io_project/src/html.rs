@@ -0,0 +1,198 @@
+// Writing the results as a web page with '--html', for sharing with people
+// who'd rather not read them in a terminal.  The page is a single file with
+// its styles written into it, so it can be mailed around or opened straight
+// from disk.
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::output::{display_name, rows, FileMatches, Row};
+use crate::Config;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+section { margin-bottom: 2em; }
+h2 { font-family: monospace; font-size: 1.1em; }
+table { border-collapse: collapse; font-family: monospace; }
+td { padding: 0 0.5em; white-space: pre; vertical-align: top; }
+td.line { color: #888; text-align: right; user-select: none; }
+tr.context td.text { color: #666; }
+tr.gap td { color: #aaa; }
+mark { background: #ffe066; }";
+
+pub fn print(config: &Config, results: &[FileMatches], out: &mut impl Write) -> io::Result<()> {
+    let matched_lines: usize = results.iter().map(|file| file.lines.len()).sum();
+    let query = escape(&config.query);
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>Results for {}</title>", query)?;
+    writeln!(out, "<style>\n{}\n</style>", STYLE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>Results for <code>{}</code></h1>", query)?;
+    writeln!(
+        out,
+        "<p>{} in {}</p>",
+        counted(matched_lines, "matching line"),
+        counted(results.len(), "file")
+    )?;
+
+    for file in results {
+        writeln!(out, "<section>")?;
+        writeln!(
+            out,
+            "<h2>{}</h2>",
+            escape(&display_name(config, &file.name))
+        )?;
+        writeln!(out, "<table>")?;
+        for row in rows(file) {
+            match row {
+                Row::Match(line) => {
+                    // A multiline match gets a row for each of its lines, with
+                    // the highlighting split up between them
+                    let mut start = 0;
+                    for (index, text) in line.text.split('\n').enumerate() {
+                        let marked = highlight(text.trim_end_matches('\r'), start, &line.ranges);
+                        write_row(out, "match", line.line_number + index, &marked)?;
+                        start += text.len() + 1;
+                    }
+                }
+                Row::Context(line_number, text) => {
+                    write_row(out, "context", line_number, &escape(text))?
+                }
+                Row::Gap => writeln!(
+                    out,
+                    "<tr class=\"gap\"><td class=\"line\">&#8942;</td><td></td></tr>"
+                )?,
+            }
+        }
+        writeln!(out, "</table>")?;
+        writeln!(out, "</section>")?;
+    }
+
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+// "1 file" or "2 files"
+fn counted(count: usize, thing: &str) -> String {
+    match count {
+        1 => format!("1 {}", thing),
+        _ => format!("{} {}s", count, thing),
+    }
+}
+
+fn write_row(out: &mut impl Write, class: &str, line_number: usize, html: &str) -> io::Result<()> {
+    writeln!(
+        out,
+        "<tr class=\"{}\"><td class=\"line\">{}</td><td class=\"text\">{}</td></tr>",
+        class, line_number, html
+    )
+}
+
+// 'text' is one line, starting 'start' bytes into the text that 'ranges'
+// point into.  The parts of the ranges that fall within the line are wrapped
+// in <mark> and everything is escaped.
+fn highlight(text: &str, start: usize, ranges: &[Range<usize>]) -> String {
+    let end = start + text.len();
+    let mut html = String::new();
+    let mut done = 0;
+    for range in ranges {
+        let from = range.start.clamp(start, end) - start;
+        let to = range.end.clamp(start, end) - start;
+        if from >= to || from < done {
+            continue;
+        }
+        html.push_str(&escape(&text[done..from]));
+        html.push_str("<mark>");
+        html.push_str(&escape(&text[from..to]));
+        html.push_str("</mark>");
+        done = to;
+    }
+    html.push_str(&escape(&text[done..]));
+    html
+}
+
+// Anything read from a file could contain markup of its own, so every piece
+// of text goes through here on its way into the page
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::output::MatchedLine;
+
+    #[test]
+    fn escapes_and_highlights() {
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt; &amp; &#39;",
+            escape("<a href=\"x\"> & '")
+        );
+        let tag = 2..5;
+        assert_eq!(
+            "a <mark>&lt;b&gt;</mark> c",
+            highlight("a <b> c", 0, &[tag])
+        );
+        // The second line of "x <b\nc> y", with the match running into it
+        let across_lines = 2..7;
+        assert_eq!(
+            "<mark>c&gt;</mark> y",
+            highlight("c> y", 5, &[across_lines])
+        );
+    }
+
+    #[test]
+    fn writes_a_page() {
+        let args: Vec<String> = ["io_project", "--html", "<b>", "page.html"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::new(&args).unwrap();
+        let results = vec![FileMatches {
+            name: "page.html".to_string(),
+            in_archive: false,
+            modified: None,
+            created: None,
+            encoding: Encoding::Utf8,
+            lines: vec![MatchedLine {
+                line_number: 4,
+                end_line_number: 4,
+                byte_offset: 0,
+                text: "<p><b>hi</b></p>".to_string(),
+                ranges: crate::match_ranges("<b>", "<p><b>hi</b></p>", true),
+            }],
+            context: vec![(1, "<html>".to_string())],
+        }];
+        let mut out = Vec::new();
+        print(&config, &results, &mut out).unwrap();
+        let page = String::from_utf8(out).unwrap();
+
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<h1>Results for <code>&lt;b&gt;</code></h1>"));
+        assert!(page.contains("<p>1 matching line in 1 file</p>"));
+        assert!(page.contains(
+            "<tr class=\"context\"><td class=\"line\">1</td><td class=\"text\">&lt;html&gt;</td></tr>\n\
+             <tr class=\"gap\">"
+        ));
+        assert!(page.contains(
+            "<td class=\"line\">4</td><td class=\"text\">&lt;p&gt;<mark>&lt;b&gt;</mark>hi&lt;/b&gt;&lt;/p&gt;</td>"
+        ));
+        assert!(!page.contains("<b>"));
+    }
+}
@@ -1,25 +1,329 @@
+use std::collections::BTreeSet;
 use std::env;
 use std::error::Error;
 use std::fs;
+use std::io;
+use std::ops::Range;
+use std::time::Instant;
+
+pub mod archive;
+pub mod encoding;
+pub mod fields;
+pub mod html;
+pub mod output;
+pub mod preprocess;
+pub mod query;
+pub mod region;
+pub mod scope;
+pub mod snapshot;
+pub mod stats;
+
+use encoding::Encoding;
+use fields::Field;
+use output::{FileMatches, MatchedLine, Sort};
+use preprocess::Preprocessor;
+use query::Expr;
+use region::{Region, Skipped};
+use scope::Scope;
+use stats::{Skip, Stats};
 
 pub struct Config {
     // Using primitive values when a complex type is more appropriate is an
     // anti-pattern known as 'primitive obsession'
     pub query: String,
-    pub filename: String,
+    pub filenames: Vec<String>,
     pub case_sensitive: bool,
+    // None means we go by the file's byte order mark, or UTF-8 if it has none
+    pub encoding: Option<Encoding>,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    // Lets the query run across line boundaries
+    pub multiline: bool,
+    // When set, each line is treated as a delimited record and only this
+    // field is searched.  Without a delimiter we go by the file extension
+    pub field: Option<Field>,
+    pub delimiter: Option<char>,
+    // Print counts and timings to stderr once the search is done
+    pub stats: bool,
+    // How many archives deep we'll go looking for files.  1 opens the archives
+    // named on the command line, 2 also opens archives inside those, and 0
+    // treats archives like any other file
+    pub max_archive_depth: usize,
+    // Only match inside these parts of Rust source files.  Empty means search
+    // everything, and files that aren't Rust are searched as normal
+    pub scopes: Vec<Scope>,
+    // A command to turn files into searchable text before they're searched
+    pub preprocessor: Option<Preprocessor>,
+    // Which order to print the files in.  None keeps the order they were
+    // searched in
+    pub sort: Option<Sort>,
+    // Leave out any line (or with only_matching, any match) that has already
+    // been printed
+    pub unique: bool,
+    // Print just the parts of each line that matched, one per line
+    pub only_matching: bool,
+    // Machine-friendly output: end file names with a NUL byte instead of ':',
+    // print paths with a different separator, or print 'path:line:col:text'
+    // for each match like Vim's :vimgrep expects
+    pub null: bool,
+    pub path_separator: Option<String>,
+    pub vimgrep: bool,
+    // Only search this part of each file.  Line numbers and offsets in the
+    // results are still counted from the start of the file
+    pub region: Option<Region>,
+    // How many lines to show before and after each match
+    pub context: usize,
+    // Write the results as a web page instead of plain text
+    pub html: bool,
+    // With '--bool', the query parsed as terms joined by AND, OR and NOT
+    pub expression: Option<Expr>,
+    // Write the results to this file so a later search can be compared with
+    // them
+    pub save_snapshot: Option<String>,
+    // Instead of the results, print how they differ from the ones saved in
+    // this file
+    pub diff_snapshot: Option<String>,
+    // Lines longer than this many characters are left out of the output, or
+    // with max_columns_preview, cut down to the part around the match
+    pub max_columns: Option<usize>,
+    pub max_columns_preview: bool,
+    // Files bigger than this many bytes aren't searched
+    pub max_filesize: Option<u64>,
 }
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &str> {
-        if args.len() < 3 {
+    pub fn new(args: &[String]) -> Result<Config, String> {
+        let mut positional = Vec::new();
+        let mut encoding = None;
+        let mut line_number = false;
+        let mut byte_offset = false;
+        let mut multiline = false;
+        let mut field = None;
+        let mut delimiter = None;
+        let mut stats = false;
+        let mut max_archive_depth = 3;
+        let mut scopes = Vec::new();
+        let mut pre_command = None;
+        let mut pre_globs = Vec::new();
+        let mut sort = None;
+        let mut unique = false;
+        let mut only_matching = false;
+        let mut null = false;
+        let mut path_separator = None;
+        let mut vimgrep = false;
+        let mut region = None;
+        let mut context = 0;
+        let mut html = false;
+        let mut boolean = false;
+        let mut save_snapshot = None;
+        let mut diff_snapshot = None;
+        let mut max_columns = None;
+        let mut max_columns_preview = false;
+        let mut max_filesize = None;
+
+        // Skip the program name, then pull out anything that looks like an
+        // option.  Whatever is left over is the query and the filenames.
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            // Options that take a value can be given as '--name value' or as
+            // '--name=value'
+            let (name, inline_value) = split_option(arg);
+            match name {
+                "-n" | "--line-number" => line_number = true,
+                "-b" | "--byte-offset" => byte_offset = true,
+                "-U" | "--multiline" => multiline = true,
+                "--stats" => stats = true,
+                "-u" | "--unique" => unique = true,
+                "-o" | "--only-matching" => only_matching = true,
+                "-0" | "--null" => null = true,
+                "--vimgrep" => vimgrep = true,
+                "--html" => html = true,
+                "--bool" => boolean = true,
+                "-C" | "--context" => {
+                    context = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--context needs a value")?
+                        .parse()
+                        .map_err(|_| "--context must be a whole number")?;
+                }
+                "--path-separator" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--path-separator needs a value")?;
+                    path_separator = Some(value.to_string());
+                }
+                "--lines" | "--bytes" => {
+                    if region.is_some() {
+                        return Err("only one of --lines and --bytes can be given".into());
+                    }
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--lines and --bytes need a range, like 100:200")?;
+                    region = Some(if name == "--lines" {
+                        Region::parse_lines(value)?
+                    } else {
+                        Region::parse_bytes(value)?
+                    });
+                }
+                "--max-columns-preview" => max_columns_preview = true,
+                "--max-columns" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--max-columns needs a value")?
+                        .parse()
+                        .map_err(|_| "--max-columns must be a whole number")?;
+                    if value == 0 {
+                        return Err("--max-columns must be at least 1".into());
+                    }
+                    max_columns = Some(value);
+                }
+                "--max-filesize" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--max-filesize needs a value")?;
+                    max_filesize =
+                        Some(parse_size(value).ok_or(
+                            "--max-filesize must be a number of bytes, like 500, 64K or 2M",
+                        )?);
+                }
+                "--save-snapshot" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--save-snapshot needs a file name")?;
+                    save_snapshot = Some(value.to_string());
+                }
+                "--diff-snapshot" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--diff-snapshot needs a file name")?;
+                    diff_snapshot = Some(value.to_string());
+                }
+                "--sort" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--sort needs a value")?;
+                    sort = Some(Sort::parse(value)?);
+                }
+                "--scope" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--scope needs a value")?;
+                    scopes = Scope::parse_list(value)?;
+                }
+                "--pre" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--pre needs a command")?;
+                    pre_command = Some(value.to_string());
+                }
+                // Can be given more than once to preprocess several kinds of
+                // file
+                "--pre-glob" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--pre-glob needs a pattern")?;
+                    pre_globs.push(value.to_string());
+                }
+                "--max-archive-depth" => {
+                    max_archive_depth = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--max-archive-depth needs a value")?
+                        .parse()
+                        .map_err(|_| "--max-archive-depth must be a whole number")?;
+                }
+                "-f" | "--field" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--field needs a value")?;
+                    field = Some(Field::parse(value)?);
+                }
+                "-d" | "--delimiter" => {
+                    let value = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--delimiter needs a value")?;
+                    delimiter = Some(fields::parse_delimiter(value)?);
+                }
+                "--encoding" => {
+                    let label = inline_value
+                        .or_else(|| args.next().map(|s| s.as_str()))
+                        .ok_or("--encoding needs a value")?;
+                    encoding = Some(Encoding::from_label(label).ok_or(
+                        "unknown encoding, expected utf-8, utf-16le, utf-16be or latin-1",
+                    )?);
+                }
+                // Everything after '--' is positional, so you can search for
+                // text that starts with a dash
+                "--" => {
+                    positional.extend(args.by_ref().cloned());
+                }
+                _ if name.starts_with('-') && name.len() > 1 => {
+                    return Err(format!("unknown option '{}'", arg));
+                }
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        if positional.len() < 2 {
             // You could just panic, but this is more meant for programmers/
             // debugging, rather than the end user
             // panic!("not enough arguments");
-            return Err("not enough arguments");
+            return Err("not enough arguments".into());
+        }
+        if delimiter.is_some() && field.is_none() {
+            return Err("--delimiter only makes sense together with --field".into());
+        }
+        if multiline && field.is_some() {
+            return Err("--multiline can't be combined with --field".into());
+        }
+        if !scopes.is_empty() && field.is_some() {
+            return Err("--scope can't be combined with --field".into());
+        }
+        if !pre_globs.is_empty() && pre_command.is_none() {
+            return Err("--pre-glob only makes sense together with --pre".into());
+        }
+        if context > 0 && (only_matching || vimgrep || unique) {
+            return Err(
+                "--context can't be combined with --only-matching, --vimgrep or --unique".into(),
+            );
         }
-        let query = args[1].clone();
-        let filename = args[2].clone();
+        // A web page can scroll sideways, and cutting lines down to size would
+        // mean cutting up the highlighting with them
+        if html && (only_matching || vimgrep || null || max_columns.is_some()) {
+            return Err(
+                "--html can't be combined with --only-matching, --vimgrep, --null or --max-columns"
+                    .into(),
+            );
+        }
+        if boolean && (multiline || field.is_some() || !scopes.is_empty()) {
+            return Err("--bool can't be combined with --multiline, --field or --scope".into());
+        }
+        if diff_snapshot.is_some() && (html || only_matching || vimgrep) {
+            return Err(
+                "--diff-snapshot can't be combined with --html, --only-matching or --vimgrep"
+                    .into(),
+            );
+        }
+        // The preprocessor's output has lines of its own, which the region's
+        // line numbers wouldn't match
+        if region.is_some() && pre_command.is_some() {
+            return Err("--lines and --bytes can't be combined with --pre".into());
+        }
+        if max_columns_preview && max_columns.is_none() {
+            return Err(
+                "--max-columns-preview only makes sense together with --max-columns".into(),
+            );
+        }
+        let preprocessor = pre_command.map(|command| Preprocessor {
+            command,
+            globs: pre_globs,
+        });
+        let query = positional.remove(0);
+        let expression = if boolean {
+            Some(Expr::parse(&query).map_err(|e| format!("bad query: {}", e))?)
+        } else {
+            None
+        };
+        let filenames = positional;
         // This is an environment variable.  We check if it's unset (there's an
         // error).  This would mean a case-sensitive search is desired.  If
         // is_err() returns false, that would mean a case-insensitive search is
@@ -30,24 +334,173 @@ impl Config {
         let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
         Ok(Config {
             query,
-            filename,
+            filenames,
             case_sensitive,
+            encoding,
+            line_number,
+            byte_offset,
+            multiline,
+            field,
+            delimiter,
+            stats,
+            max_archive_depth,
+            scopes,
+            preprocessor,
+            sort,
+            unique,
+            only_matching,
+            null,
+            path_separator,
+            vimgrep,
+            region,
+            context,
+            html,
+            expression,
+            save_snapshot,
+            diff_snapshot,
+            max_columns,
+            max_columns_preview,
+            max_filesize,
         })
     }
 }
 
+// Splits '--name=value' into its two halves.  Short options and options
+// without an '=' come back with no value
+fn split_option(arg: &str) -> (&str, Option<&str>) {
+    match arg.split_once('=') {
+        Some((name, value)) if arg.starts_with("--") => (name, Some(value)),
+        _ => (arg, None),
+    }
+}
+
+// A size in bytes, optionally followed by K, M or G for kibibytes, mebibytes
+// or gibibytes
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, multiplier) = match value.chars().last()?.to_ascii_uppercase() {
+        'K' => (&value[..value.len() - 1], 1 << 10),
+        'M' => (&value[..value.len() - 1], 1 << 20),
+        'G' => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
 // Learn more about trait objects in Ch 17
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
+    let started = Instant::now();
+    let mut stats = Stats::default();
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
-    } else {
-        search_case_insensitive(&config.query, &contents)
-    };
+    let mut results = Vec::new();
+
+    // One bad file shouldn't stop us searching the rest, so problems are
+    // reported as we go and only turned into an error at the end
+    for filename in &config.filenames {
+        let metadata = fs::metadata(filename).ok();
+        let file = FileMatches {
+            name: filename.clone(),
+            in_archive: false,
+            modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+            created: metadata.as_ref().and_then(|m| m.created().ok()),
+            encoding: Encoding::Utf8,
+            lines: Vec::new(),
+            context: Vec::new(),
+        };
+        // Checked before the file is read, since not reading it is the point
+        let size = metadata.as_ref().map_or(0, |m| m.len());
+        if config.max_filesize.is_some_and(|max| size > max) {
+            skip(filename, Box::new(Skip("too large")), &mut stats);
+            continue;
+        }
+        // Part of an archive can't be unpacked, so these are left out rather
+        // than searched from the middle
+        let archive = archive::Kind::from_name(filename).is_some() && config.max_archive_depth > 0;
+        if config.region.is_some() && archive {
+            skip(
+                filename,
+                Box::new(Skip("--lines and --bytes don't work on archives")),
+                &mut stats,
+            );
+            continue;
+        }
+        // read_to_string only understands UTF-8, so we read the raw bytes and
+        // transcode them ourselves
+        let read = match &config.region {
+            Some(region) => region.read(filename),
+            None => fs::read(filename).map(|bytes| (bytes, Skipped::default())),
+        };
+        match read {
+            Ok((bytes, skipped)) => {
+                let first_new = results.len();
+                search_bytes(&config, file, &bytes, 0, &mut stats, &mut results);
+                // The search only saw the part of the file we read, so it
+                // counted from the start of that
+                for file in &mut results[first_new..] {
+                    for line in &mut file.lines {
+                        line.line_number += skipped.lines;
+                        line.end_line_number += skipped.lines;
+                        line.byte_offset += skipped.bytes;
+                    }
+                    for (line_number, _) in &mut file.context {
+                        *line_number += skipped.lines;
+                    }
+                }
+            }
+            Err(e) => skip(filename, e.into(), &mut stats),
+        }
+    }
+
+    if let Some(sort) = config.sort {
+        output::sort(&mut results, sort);
+    }
+    // The old snapshot is read before the new one is written, so the same
+    // file can be given to both options to compare against the last run.
+    // Everything in a file that couldn't be searched would look like it had
+    // disappeared, so there's no comparing without every file.
+    match &config.diff_snapshot {
+        Some(path) if stats.errors == 0 => {
+            let old = snapshot::load(path)
+                .map_err(|e| format!("couldn't read snapshot {}: {}", path, e))?;
+            let changes = snapshot::diff(old, snapshot::entries(&results));
+            let counts = snapshot::print_diff(&changes, &mut io::stdout().lock())?;
+            eprintln!("{}", counts);
+        }
+        // Nothing to show.  The error at the end says why.
+        Some(_) => {}
+        None if config.html => html::print(&config, &results, &mut io::stdout().lock())?,
+        None => output::print(&config, &results, &mut io::stdout().lock())?,
+    }
+
+    // A snapshot missing the files that couldn't be searched would make them
+    // look like they'd stopped matching next time, so none is saved
+    if let Some(path) = config.save_snapshot.as_ref().filter(|_| stats.errors == 0) {
+        snapshot::save(path, &snapshot::entries(&results))
+            .map_err(|e| format!("couldn't save snapshot {}: {}", path, e))?;
+    }
 
-    for line in results {
-        println!("{}", line);
+    if config.stats {
+        stats.elapsed = started.elapsed();
+        eprint!("{}", stats);
+    }
+
+    if stats.errors > 0 {
+        let mut message = match stats.errors {
+            1 => "1 file could not be searched".to_string(),
+            errors => format!("{} files could not be searched", errors),
+        };
+        let left_undone = match (
+            config.diff_snapshot.is_some(),
+            config.save_snapshot.is_some(),
+        ) {
+            (true, true) => Some("compared or saved"),
+            (true, false) => Some("compared"),
+            (false, true) => Some("saved"),
+            (false, false) => None,
+        };
+        if let Some(left_undone) = left_undone {
+            message.push_str(&format!(", so the snapshot wasn't {}", left_undone));
+        }
+        return Err(message.into());
     }
 
     // Putting the '()' within the 'Ok()' is the idomatic way to do it.  It says
@@ -55,6 +508,356 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn skip(name: &str, error: Box<dyn Error>, stats: &mut Stats) {
+    if !error.is::<Skip>() {
+        eprintln!("{}: {}", name, error);
+    }
+    stats.add_skipped(error.as_ref());
+}
+
+// Searches one file's worth of bytes, which may have come from disk or from
+// inside an archive, and adds what it finds to results.  'depth' is how many
+// archives we're inside of.  Archive members are named
+// 'archive.zip:path/in/archive', so results inside them come out as
+// 'archive.zip:path/in/archive:line'.
+fn search_bytes(
+    config: &Config,
+    file: FileMatches,
+    bytes: &[u8],
+    depth: usize,
+    stats: &mut Stats,
+    results: &mut Vec<FileMatches>,
+) {
+    let name = file.name.clone();
+    let name = name.as_str();
+
+    // Whatever the preprocessor prints is searched as text, even if the file
+    // it came from was an archive
+    let preprocessor = config
+        .preprocessor
+        .as_ref()
+        .filter(|pre| pre.applies_to(name));
+    if let Some(preprocessor) = preprocessor {
+        let result = preprocessor
+            .run(name, bytes)
+            .map_err(|e| e.into())
+            .and_then(|output| search_text(config, file, &output, stats, results));
+        if let Err(e) = result {
+            skip(name, e, stats);
+        }
+        return;
+    }
+
+    match archive::Kind::from_name(name) {
+        Some(kind) if depth < config.max_archive_depth => {
+            // Files on disk have already been checked against --max-filesize,
+            // but those in archives are checked as they're unpacked
+            let limit = config.max_filesize.unwrap_or(archive::DEFAULT_MEMBER_LIMIT);
+            match archive::members(kind, bytes, limit) {
+                Ok(members) => {
+                    for member in members {
+                        let inner = file.member(&member.path);
+                        match &member.data {
+                            Some(data) => {
+                                search_bytes(config, inner, data, depth + 1, stats, results)
+                            }
+                            None => skip(&inner.name, Box::new(Skip("too large")), stats),
+                        }
+                    }
+                }
+                Err(e) => skip(name, e.into(), stats),
+            }
+        }
+        Some(_) if depth > 0 => skip(name, Box::new(Skip("archive nested too deeply")), stats),
+        _ if depth > 0 && config.encoding.is_none() && archive::looks_binary(bytes) => {
+            skip(name, Box::new(Skip("binary")), stats)
+        }
+        _ => {
+            if let Err(e) = search_text(config, file, bytes, stats, results) {
+                skip(name, e, stats);
+            }
+        }
+    }
+}
+
+fn search_text(
+    config: &Config,
+    mut file: FileMatches,
+    bytes: &[u8],
+    stats: &mut Stats,
+    results: &mut Vec<FileMatches>,
+) -> Result<(), Box<dyn Error>> {
+    let filename = file.name.as_str();
+    let decoded = encoding::decode(bytes, config.encoding)?;
+    let mut offsets = decoded.offsets();
+
+    let found = if let Some(field) = &config.field {
+        let delimiter = config
+            .delimiter
+            .unwrap_or_else(|| fields::default_delimiter(filename));
+        fields::search_fields(config, field, delimiter, &decoded.text)?
+    } else if !config.scopes.is_empty() && filename.ends_with(".rs") {
+        scope::search_scoped(config, &config.scopes, &decoded.text)
+    } else if config.multiline {
+        search_multiline(config, &decoded.text)
+    } else {
+        search_lines(config, &decoded.text)
+    };
+
+    if config.stats {
+        stats.add_file(bytes.len(), &decoded.text, &found);
+    }
+
+    if config.context > 0 {
+        file.context = context_lines(config.context, &decoded.text, &found);
+    }
+    file.encoding = decoded.encoding;
+    file.lines = found
+        .into_iter()
+        .map(|found| MatchedLine {
+            line_number: found.line_number,
+            end_line_number: found.end_line_number,
+            byte_offset: offsets.original(found.offset),
+            text: found.line.to_string(),
+            ranges: found.ranges,
+        })
+        .collect();
+    if !file.lines.is_empty() {
+        results.push(file);
+    }
+    Ok(())
+}
+
+// The lines within 'context' lines of a match, leaving out the matches
+// themselves.  Where two matches are close together their context is shared
+// rather than repeated.
+fn context_lines(context: usize, contents: &str, found: &[Match]) -> Vec<(usize, String)> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut wanted = BTreeSet::new();
+    for found in found {
+        let first = found.line_number.saturating_sub(context).max(1);
+        let last = (found.end_line_number + context).min(lines.len());
+        wanted.extend(first..=last);
+    }
+    for found in found {
+        for line_number in found.line_number..=found.end_line_number {
+            wanted.remove(&line_number);
+        }
+    }
+    wanted
+        .into_iter()
+        .map(|line_number| (line_number, lines[line_number - 1].to_string()))
+        .collect()
+}
+
+// A matching line, along with where it was found.  'offset' is the byte
+// offset of the start of the line within the searched text.  In multiline mode
+// a match can cover several lines, in which case 'line' holds all of them and
+// 'end_line_number' is the number of the last one.  'ranges' are where the
+// query itself was found, as byte ranges into 'line'
+pub struct Match<'a> {
+    pub line_number: usize,
+    pub end_line_number: usize,
+    pub offset: usize,
+    pub line: &'a str,
+    pub ranges: Vec<Range<usize>>,
+}
+
+// Does the same job as search and search_case_insensitive, but keeps track of
+// line numbers and offsets as it goes
+pub fn search_lines<'a>(config: &Config, contents: &'a str) -> Vec<Match<'a>> {
+    let mut results = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let is_match = match &config.expression {
+            Some(expression) => expression.matches(line, config.case_sensitive),
+            None if config.case_sensitive => line.contains(&config.query),
+            None => contains_ignoring_case(line, &config.query),
+        };
+        if is_match {
+            let ranges = match &config.expression {
+                Some(expression) => expression.ranges(line, config.case_sensitive),
+                None => match_ranges(&config.query, line, config.case_sensitive),
+            };
+            results.push(Match {
+                line_number: index + 1,
+                end_line_number: index + 1,
+                // lines() hands back slices of contents, so the distance
+                // between the two pointers is the offset of the line
+                offset: line.as_ptr() as usize - contents.as_ptr() as usize,
+                line,
+                ranges,
+            });
+        }
+    }
+    results
+}
+
+// Searches the whole of contents at once instead of line by line, so a query
+// containing newlines can match across line boundaries
+pub fn search_multiline<'a>(config: &Config, contents: &'a str) -> Vec<Match<'a>> {
+    let ranges = if config.query.contains('\n') && contents.contains("\r\n") {
+        crlf_match_ranges(&config.query, contents, config.case_sensitive)
+    } else {
+        match_ranges(&config.query, contents, config.case_sensitive)
+    };
+    lines_around(contents, ranges)
+}
+
+// A '\n' in the query should match the end of a line in a Windows file too,
+// where lines end with '\r\n'.  So the search is done on a copy with the '\r's
+// taken out of those line endings, and the ranges found are moved back to
+// where they are in contents.
+fn crlf_match_ranges(query: &str, contents: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    let stripped = contents.replace("\r\n", "\n");
+    // Where each '\r' that was taken out would have been in the copy.  A
+    // range in the copy moves along by one for each of those before it.
+    let removed: Vec<usize> = contents
+        .match_indices("\r\n")
+        .enumerate()
+        .map(|(count, (index, _))| index - count)
+        .collect();
+    let moved_before = |position: usize| removed.partition_point(|&r| r < position);
+    // A range starting on a line ending starts at its '\r'
+    let moved_up_to = |position: usize| removed.partition_point(|&r| r <= position);
+
+    match_ranges(query, &stripped, case_sensitive)
+        .into_iter()
+        .map(|range| range.start + moved_up_to(range.start)..range.end + moved_before(range.end))
+        .collect()
+}
+
+// Turns byte ranges found in contents into matches.  Each range is widened out
+// to the full lines it touches, and ranges that share a line are merged.  The
+// ranges need to be in order.
+pub fn lines_around<'a>(contents: &'a str, ranges: Vec<Range<usize>>) -> Vec<Match<'a>> {
+    let mut results: Vec<Match> = Vec::new();
+    let mut line_number = 1;
+    let mut counted = 0;
+
+    for range in ranges {
+        line_number += contents[counted..range.start].matches('\n').count();
+        counted = range.start;
+
+        // A match that ends on a newline finishes on the line before it
+        let last = if range.end > range.start && contents[..range.end].ends_with('\n') {
+            range.end - 1
+        } else {
+            range.end
+        };
+        let start = contents[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let end = contents[last..]
+            .find('\n')
+            .map_or(contents.len(), |i| last + i);
+        let end = if contents[..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        let end_line_number = line_number + contents[range.start..last].matches('\n').count();
+
+        if let Some(previous) = results.last_mut() {
+            if start <= previous.offset + previous.line.len() {
+                previous.line =
+                    &contents[previous.offset..end.max(previous.offset + previous.line.len())];
+                previous.end_line_number = previous.end_line_number.max(end_line_number);
+                previous
+                    .ranges
+                    .push(range.start - previous.offset..range.end - previous.offset);
+                continue;
+            }
+        }
+        let relative = range.start - start..range.end - start;
+        results.push(Match {
+            line_number,
+            end_line_number,
+            offset: start,
+            line: &contents[start..end],
+            ranges: vec![relative],
+        });
+    }
+    results
+}
+
+// Finds every place query appears in text, as byte ranges into text.  Matches
+// don't overlap.  The case-insensitive version can't just lowercase text and
+// search that, because lowercasing can change how many bytes a character
+// takes up, and then the ranges wouldn't line up with text anymore.
+pub fn match_ranges(query: &str, text: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    if case_sensitive {
+        return text
+            .match_indices(query)
+            .map(|(start, found)| start..start + found.len())
+            .collect();
+    }
+
+    let query = lowercase(query);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while let Some(range) = find_ignoring_case(text, &query, start) {
+        // An empty query is found everywhere, so we step over a character
+        // each time to get past it
+        start = match text[range.end..].chars().next() {
+            _ if !range.is_empty() => range.end,
+            Some(c) => range.end + c.len_utf8(),
+            None => text.len() + 1,
+        };
+        ranges.push(range);
+    }
+    ranges
+}
+
+// Whether query appears in text, ignoring case.  Every case-insensitive search
+// comes through here or match_ranges, which find matches the same way, so
+// searching line by line and searching the whole file agree on what matched.
+// Lowercasing both and using str::contains wouldn't: 'İ' lowercases to 'i'
+// followed by a combining dot, and an 'i' would be found inside it.
+pub fn contains_ignoring_case(text: &str, query: &str) -> bool {
+    find_ignoring_case(text, &lowercase(query), 0).is_some()
+}
+
+// Lowercases one character at a time.  str::to_lowercase follows the Greek
+// rule where 'Σ' at the end of a word becomes 'ς' rather than 'σ', so "ΑΣ"
+// lowercases to "ας" while "Σ" on its own lowercases to "σ", and a line that
+// contains the query could stop containing it once both were lowercased.
+fn lowercase(text: &str) -> Vec<char> {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
+
+// The first place at or after 'start' where the already lowercased query
+// appears in text.  Matches start and end on whole characters of text.
+fn find_ignoring_case(text: &str, query: &[char], mut start: usize) -> Option<Range<usize>> {
+    while start <= text.len() {
+        if let Some(len) = lowercase_prefix_len(&text[start..], query) {
+            return Some(start..start + len);
+        }
+        start += text[start..].chars().next()?.len_utf8();
+    }
+    None
+}
+
+// If text starts with query (ignoring case), returns how many bytes of text
+// that covers
+fn lowercase_prefix_len(text: &str, query: &[char]) -> Option<usize> {
+    let mut matched = 0;
+    for (index, c) in text.char_indices() {
+        if matched == query.len() {
+            return Some(index);
+        }
+        for lower in c.to_lowercase() {
+            if query.get(matched) != Some(&lower) {
+                return None;
+            }
+            matched += 1;
+        }
+    }
+    if matched == query.len() {
+        Some(text.len())
+    } else {
+        None
+    }
+}
+
 // We only put 'a on contents because that's what it should be a reference to
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     let mut results = Vec::new();
@@ -68,11 +871,10 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 }
 
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
     let mut results = Vec::new();
 
     for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
+        if contains_ignoring_case(line, query) {
             results.push(line);
         }
     }
@@ -120,4 +922,173 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        let config = Config::new(&args(&[
+            "io_project",
+            "-n",
+            "--encoding=UTF-16LE",
+            "to",
+            "poem.txt",
+        ]))
+        .unwrap();
+        assert_eq!("to", config.query);
+        assert_eq!(vec!["poem.txt"], config.filenames);
+        assert!(config.line_number);
+        assert_eq!(Some(Encoding::Utf16Le), config.encoding);
+
+        assert!(Config::new(&args(&[
+            "io_project",
+            "--encoding",
+            "ebcdic",
+            "to",
+            "poem.txt"
+        ]))
+        .is_err());
+        assert_eq!(
+            Some("unknown option '--colour'".to_string()),
+            Config::new(&args(&["io_project", "--colour", "to", "poem.txt"])).err()
+        );
+        assert_eq!(
+            Some(
+                "--html can't be combined with --only-matching, --vimgrep, --null or --max-columns"
+                    .to_string()
+            ),
+            Config::new(&args(&[
+                "io_project",
+                "--html",
+                "--max-columns=80",
+                "to",
+                "poem.txt"
+            ]))
+            .err()
+        );
+        assert_eq!(
+            Some("--lines and --bytes can't be combined with --pre".to_string()),
+            Config::new(&args(&[
+                "io_project",
+                "--lines=1:10",
+                "--pre=pdftotext",
+                "to",
+                "poem.txt"
+            ]))
+            .err()
+        );
+    }
+
+    #[test]
+    fn boolean_queries() {
+        let config = Config::new(&args(&[
+            "io_project",
+            "--bool",
+            "safe AND NOT fast",
+            "poem.txt",
+        ]))
+        .unwrap();
+        let contents = "safe, fast, productive.\nsafe and sound.\n";
+        let results = search_lines(&config, contents);
+        assert_eq!(1, results.len());
+        assert_eq!(2, results[0].line_number);
+
+        assert_eq!(
+            Err("bad query: missing ')' for the '(' at column 1".to_string()),
+            Config::new(&args(&["io_project", "--bool", "(safe", "poem.txt"])).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(Some(500), parse_size("500"));
+        assert_eq!(Some(64 * 1024), parse_size("64K"));
+        assert_eq!(Some(2 * 1024 * 1024), parse_size("2m"));
+        assert_eq!(None, parse_size("M"));
+        assert_eq!(None, parse_size("lots"));
+    }
+
+    #[test]
+    fn line_numbers_and_offsets() {
+        let config = Config::new(&args(&["io_project", "me", "poem.txt"])).unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Trust me.";
+
+        let results = search_lines(&config, contents);
+        assert_eq!(1, results.len());
+        assert_eq!(3, results[0].line_number);
+        assert_eq!(30, results[0].offset);
+        assert_eq!("Trust me.", results[0].line);
+    }
+
+    #[test]
+    fn multiline_match() {
+        let config = Config::new(&args(&[
+            "io_project",
+            "-U",
+            "fast,\nproductive",
+            "poem.txt",
+        ]))
+        .unwrap();
+        let contents = "\
+Rust:
+safe, fast,
+productive.
+Pick three.";
+
+        let results = search_multiline(&config, contents);
+        assert_eq!(1, results.len());
+        assert_eq!(2, results[0].line_number);
+        assert_eq!(3, results[0].end_line_number);
+        assert_eq!("safe, fast,\nproductive.", results[0].line);
+    }
+
+    #[test]
+    fn multiline_match_with_crlf() {
+        let config = Config::new(&args(&[
+            "io_project",
+            "-U",
+            "fast,\nproductive",
+            "poem.txt",
+        ]))
+        .unwrap();
+        let contents = "Rust:\r\nsafe, fast,\r\nproductive.\r\nPick three.";
+
+        let results = search_multiline(&config, contents);
+        assert_eq!(1, results.len());
+        assert_eq!(2, results[0].line_number);
+        assert_eq!(3, results[0].end_line_number);
+        assert_eq!("safe, fast,\r\nproductive.", results[0].line);
+        assert_eq!(vec![6..23], results[0].ranges);
+    }
+
+    #[test]
+    fn multiline_merges_matches_on_the_same_line() {
+        let config = Config::new(&args(&["io_project", "-U", "t", "poem.txt"])).unwrap();
+        let contents = "Rust:\ntrust, test,\nPick three.";
+
+        let results = search_multiline(&config, contents);
+        assert_eq!(3, results.len());
+        assert_eq!("trust, test,", results[1].line);
+        assert_eq!(2, results[1].end_line_number);
+    }
+
+    #[test]
+    fn case_insensitive_ranges() {
+        // 'İ' is two bytes but lowercases to three, so the ranges have to be
+        // worked out against the original text
+        assert_eq!(vec![1..4], match_ranges("İs", "xİStanbul", false));
+        assert_eq!(vec![3..7], match_ranges("rUsT", "in rust", false));
+        assert_eq!(vec![3..7], match_ranges("rust", "in rust", true));
+        // The 'i' that 'İ' lowercases to isn't a character of the text, so
+        // it can't be found on its own
+        assert!(match_ranges("i", "İ", false).is_empty());
+        assert!(!contains_ignoring_case("İ", "i"));
+        assert!(contains_ignoring_case("xİStanbul", "İs"));
+        assert_eq!(vec![0..0, 2..2], match_ranges("", "İ", false));
+    }
 }
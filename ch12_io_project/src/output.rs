@@ -0,0 +1,454 @@
+// Printing what we found.  run collects the results from every file first so
+// that they can be sorted and duplicates dropped before anything is printed.
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::time::SystemTime;
+
+use crate::encoding::Encoding;
+use crate::Config;
+
+// One searched file and the lines that matched in it
+pub struct FileMatches {
+    pub name: String,
+    // Files inside an archive are named 'archive.zip:path/in/archive'
+    pub in_archive: bool,
+    // Taken from the file on disk, so files inside an archive share the
+    // archive's times.  Not every platform records when a file was created
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    // What the file was decoded from, for working out byte offsets
+    pub encoding: Encoding,
+    pub lines: Vec<MatchedLine>,
+    // With '--context', the lines around the matches that didn't match
+    // themselves, as line numbers and text, in order
+    pub context: Vec<(usize, String)>,
+}
+
+// The owned version of a Match, so it can outlive the text it was found in
+pub struct MatchedLine {
+    pub line_number: usize,
+    pub end_line_number: usize,
+    // Where the line starts in the file as it is on disk
+    pub byte_offset: usize,
+    pub text: String,
+    // Where the query was found, as byte ranges into text
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl FileMatches {
+    // An empty FileMatches for a file inside this one, which is an archive
+    pub fn member(&self, path: &str) -> FileMatches {
+        FileMatches {
+            name: format!("{}:{}", self.name, path),
+            in_archive: true,
+            modified: self.modified,
+            created: self.created,
+            encoding: self.encoding,
+            lines: Vec::new(),
+            context: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sort {
+    Path,
+    // Oldest first
+    Modified,
+    Created,
+    // Most matching lines first
+    Matches,
+}
+
+impl Sort {
+    pub fn parse(value: &str) -> Result<Sort, &'static str> {
+        match value {
+            "path" => Ok(Sort::Path),
+            "modified" => Ok(Sort::Modified),
+            "created" => Ok(Sort::Created),
+            "matches" => Ok(Sort::Matches),
+            _ => Err("--sort must be path, modified, created or matches"),
+        }
+    }
+}
+
+// Sorting is by file, so lines stay in order within each file.  sort_by_key is
+// stable, which means files that tie stay in the order they were searched.
+// Files with no time recorded go after those with one.
+pub fn sort(results: &mut [FileMatches], sort: Sort) {
+    match sort {
+        Sort::Path => results.sort_by(|a, b| a.name.cmp(&b.name)),
+        Sort::Modified => results.sort_by_key(|file| (file.modified.is_none(), file.modified)),
+        Sort::Created => results.sort_by_key(|file| (file.created.is_none(), file.created)),
+        Sort::Matches => results.sort_by_key(|file| std::cmp::Reverse(file.lines.len())),
+    }
+}
+
+// One line of output for a file, in the order they appear in it
+pub enum Row<'a> {
+    Match(&'a MatchedLine),
+    Context(usize, &'a str),
+    // Some lines were left out between the last row and the next
+    Gap,
+}
+
+// Puts a file's matches and context lines back together in line order,
+// marking where lines were left out between them
+pub fn rows(file: &FileMatches) -> Vec<Row<'_>> {
+    let mut rows = Vec::new();
+    let mut matches = file.lines.iter().peekable();
+    let mut context = file.context.iter().peekable();
+    // The line number we'd expect the next row to have if nothing was skipped
+    let mut next = None;
+
+    loop {
+        let take_match = match (matches.peek(), context.peek()) {
+            (Some(line), Some((number, _))) => line.line_number < *number,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        let (row, first, last) = if take_match {
+            let line = matches.next().unwrap();
+            (Row::Match(line), line.line_number, line.end_line_number)
+        } else {
+            let (number, text) = context.next().unwrap();
+            (Row::Context(*number, text.as_str()), *number, *number)
+        };
+        if next.is_some_and(|next| first > next) {
+            rows.push(Row::Gap);
+        }
+        next = Some(last + 1);
+        rows.push(row);
+    }
+    rows
+}
+
+// Paths are printed with '/' swapped for '--path-separator', if one was
+// given.  The ':' that separates an archive from the files inside it is left
+// alone.
+pub fn display_name<'a>(config: &Config, name: &'a str) -> Cow<'a, str> {
+    match &config.path_separator {
+        Some(separator) => Cow::Owned(name.replace(['/', std::path::MAIN_SEPARATOR], separator)),
+        None => Cow::Borrowed(name),
+    }
+}
+
+pub fn print(config: &Config, results: &[FileMatches], out: &mut impl Write) -> io::Result<()> {
+    // With a single file the filename would be the same on every line, so it's
+    // only shown when there's more than one, or we're in an archive.  Editors
+    // reading --vimgrep output always need it.
+    let show_names = config.filenames.len() > 1 || config.vimgrep;
+    // A file name can't contain a NUL byte, so unlike ':' it can always be told
+    // apart from the name
+    let after_name = if config.null { "\0" } else { ":" };
+    let mut seen = HashSet::new();
+    // Like grep, a line of '--' separates groups of lines that aren't next to
+    // each other, but only when there's context to make the groups
+    let with_context = config.context > 0;
+    let mut printed_any = false;
+
+    for file in results {
+        let name = display_name(config, &file.name);
+        let show_name = show_names || file.in_archive;
+        if with_context && printed_any && !file.lines.is_empty() {
+            writeln!(out, "--")?;
+        }
+        for row in rows(file) {
+            let line = match row {
+                Row::Match(line) => line,
+                Row::Context(number, text) => {
+                    // Context lines use '-' where matches use ':', so they can
+                    // be told apart.  They aren't given byte offsets.
+                    if show_name {
+                        write!(out, "{}{}", name, if config.null { "\0" } else { "-" })?;
+                    }
+                    if config.line_number {
+                        write!(out, "{}-", number)?;
+                    }
+                    writeln!(out, "{}", fit(config, text, 0, 0))?;
+                    continue;
+                }
+                Row::Gap if with_context => {
+                    writeln!(out, "--")?;
+                    continue;
+                }
+                Row::Gap => continue,
+            };
+            printed_any = true;
+            // Each item is the text to print and how far into the line it
+            // starts, which we need for the line number and byte offset.
+            // --vimgrep prints every match on its own, along with the line it
+            // starts on.
+            let items: Vec<(&str, usize)> = if config.only_matching {
+                line.ranges
+                    .iter()
+                    .map(|range| (&line.text[range.clone()], range.start))
+                    .collect()
+            } else if config.vimgrep && !line.ranges.is_empty() {
+                line.ranges
+                    .iter()
+                    .map(|range| (line_containing(&line.text, range.start), range.start))
+                    .collect()
+            } else {
+                vec![(line.text.as_str(), 0)]
+            };
+
+            for (text, start) in items {
+                // insert() returns false if the text was already in the set
+                if config.unique && !seen.insert(text) {
+                    continue;
+                }
+                let before = &line.text[..start];
+                let line_number = line.line_number + before.matches('\n').count();
+                let end_line_number = line_number + text.matches('\n').count();
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+                // Where in the text the match is, and how many there are, for
+                // when the text is too long to print in full
+                let (focus, matches) = if config.only_matching {
+                    (0, 1)
+                } else if config.vimgrep {
+                    (start - line_start, 1)
+                } else {
+                    let first = line.ranges.first().map_or(0, |range| range.start);
+                    (first, line.ranges.len().max(1))
+                };
+                let text = fit(config, text, focus, matches);
+
+                if show_name {
+                    write!(out, "{}{}", name, after_name)?;
+                }
+                if config.vimgrep {
+                    // Vim counts columns in bytes, starting from 1
+                    writeln!(out, "{}:{}:{}", line_number, start - line_start + 1, text)?;
+                    continue;
+                }
+                if config.line_number && end_line_number != line_number {
+                    write!(out, "{}-{}:", line_number, end_line_number)?;
+                } else if config.line_number {
+                    write!(out, "{}:", line_number)?;
+                }
+                if config.byte_offset {
+                    write!(
+                        out,
+                        "{}:",
+                        line.byte_offset + file.encoding.encoded_len(before)
+                    )?;
+                }
+                writeln!(out, "{}", text)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Minified files can have matches on lines that are megabytes long.  With
+// '--max-columns', text longer than that many characters is replaced with a
+// note saying it was left out, or with '--max-columns-preview', cut down to a
+// window around the match at 'focus' (a byte offset into text).
+fn fit<'a>(config: &Config, text: &'a str, focus: usize, matches: usize) -> Cow<'a, str> {
+    let max = match config.max_columns {
+        Some(max) if text.chars().count() > max => max,
+        _ => return Cow::Borrowed(text),
+    };
+    if !config.max_columns_preview {
+        return Cow::Owned(match matches {
+            0 => "[omitted long line]".to_string(),
+            1 => "[omitted long line with 1 match]".to_string(),
+            _ => format!("[omitted long line with {} matches]", matches),
+        });
+    }
+
+    // Start the window a little before the match, so there's some of what
+    // leads up to it, but most of the room goes to the match and what follows
+    let total = text.chars().count();
+    let focus = text[..focus].chars().count();
+    let first = focus.saturating_sub(max / 4).min(total - max);
+    let byte_at = |chars: usize| {
+        text.char_indices()
+            .nth(chars)
+            .map_or(text.len(), |(i, _)| i)
+    };
+    let window = &text[byte_at(first)..byte_at(first + max)];
+    Cow::Owned(format!(
+        "{}{}{}",
+        if first > 0 { "..." } else { "" },
+        window,
+        if first + max < total { "..." } else { "" }
+    ))
+}
+
+// The single line within text that the byte at 'index' is on
+fn line_containing(text: &str, index: usize) -> &str {
+    let start = text[..index].rfind('\n').map_or(0, |i| i + 1);
+    let end = text[index..].find('\n').map_or(text.len(), |i| index + i);
+    text[start..end].trim_end_matches('\r')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, lines: &[(usize, &str)]) -> FileMatches {
+        FileMatches {
+            name: name.to_string(),
+            in_archive: false,
+            modified: None,
+            created: None,
+            encoding: Encoding::Utf8,
+            lines: lines
+                .iter()
+                .map(|&(line_number, text)| MatchedLine {
+                    line_number,
+                    end_line_number: line_number,
+                    byte_offset: 0,
+                    text: text.to_string(),
+                    ranges: crate::match_ranges("frog", text, true),
+                })
+                .collect(),
+            context: Vec::new(),
+        }
+    }
+
+    fn printed(args: &[&str], results: &[FileMatches]) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let config = Config::new(&args).unwrap();
+        let mut out = Vec::new();
+        print(&config, results, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn sorts_by_path_and_matches() {
+        let mut results = vec![
+            file("b.txt", &[(1, "a frog")]),
+            file("a.txt", &[(1, "a frog"), (2, "frog frog")]),
+        ];
+        sort(&mut results, Sort::Path);
+        assert_eq!("a.txt", results[0].name);
+        sort(&mut results, Sort::Matches);
+        assert_eq!("a.txt", results[0].name);
+
+        results.reverse();
+        sort(&mut results, Sort::Matches);
+        assert_eq!("a.txt", results[0].name);
+    }
+
+    #[test]
+    fn unique_lines() {
+        let results = vec![
+            file("a.txt", &[(1, "a frog"), (2, "frog frog")]),
+            file("b.txt", &[(4, "a frog")]),
+        ];
+        assert_eq!(
+            "a.txt:a frog\na.txt:frog frog\n",
+            printed(
+                &["io_project", "--unique", "frog", "a.txt", "b.txt"],
+                &results
+            )
+        );
+    }
+
+    #[test]
+    fn only_matching() {
+        let results = vec![file("a.txt", &[(2, "frog, a frog")])];
+        assert_eq!(
+            "2:0:frog\n2:8:frog\n",
+            printed(&["io_project", "-o", "-n", "-b", "frog", "a.txt"], &results)
+        );
+        assert_eq!(
+            "frog\n",
+            printed(&["io_project", "-o", "--unique", "frog", "a.txt"], &results)
+        );
+    }
+
+    #[test]
+    fn context_lines() {
+        let mut results = vec![
+            file("a.txt", &[(2, "a frog"), (7, "frog")]),
+            file("b.txt", &[(1, "frog")]),
+        ];
+        results[0].context = vec![
+            (1, "in a bog".to_string()),
+            (3, "sat".to_string()),
+            (8, "".to_string()),
+        ];
+        assert_eq!(
+            "a.txt-1-in a bog\na.txt:2:a frog\na.txt-3-sat\n--\n\
+             a.txt:7:frog\na.txt-8-\n--\nb.txt:1:frog\n",
+            printed(
+                &["io_project", "-n", "-C", "1", "frog", "a.txt", "b.txt"],
+                &results
+            )
+        );
+    }
+
+    #[test]
+    fn long_lines() {
+        let long = format!("{}a frog{}", "x".repeat(20), "y".repeat(20));
+        let results = vec![file("min.js", &[(1, &long), (2, "frog")])];
+        assert_eq!(
+            "[omitted long line with 1 match]\nfrog\n",
+            printed(
+                &["io_project", "--max-columns", "12", "frog", "min.js"],
+                &results
+            )
+        );
+        assert_eq!(
+            "...xa frogyyyyy...\nfrog\n",
+            printed(
+                &[
+                    "io_project",
+                    "--max-columns=12",
+                    "--max-columns-preview",
+                    "frog",
+                    "min.js"
+                ],
+                &results
+            )
+        );
+    }
+
+    #[test]
+    fn machine_readable_names() {
+        let results = vec![
+            file("dir/odd:name.txt", &[(3, "a frog")]),
+            file("b.txt", &[(1, "frog")]),
+        ];
+        assert_eq!(
+            "dir/odd:name.txt\0a frog\nb.txt\0frog\n",
+            printed(
+                &["io_project", "-0", "frog", "dir/odd:name.txt", "b.txt"],
+                &results
+            )
+        );
+        assert_eq!(
+            "dir\\odd:name.txt:a frog\nb.txt:frog\n",
+            printed(
+                &["io_project", "--path-separator=\\", "frog", "a", "b"],
+                &results
+            )
+        );
+    }
+
+    #[test]
+    fn vimgrep() {
+        let mut results = vec![file("a.txt", &[(2, "frog, a frog")])];
+        assert_eq!(
+            "a.txt:2:1:frog, a frog\na.txt:2:9:frog, a frog\n",
+            printed(&["io_project", "--vimgrep", "frog", "a.txt"], &results)
+        );
+
+        // A multiline match is reported on the line it starts on
+        results[0].lines[0].text = "a frog\nin a bog".to_string();
+        let across_lines = 2..13;
+        results[0].lines[0].ranges = vec![across_lines];
+        assert_eq!(
+            "a.txt:2:3:a frog\n",
+            printed(&["io_project", "--vimgrep", "frog", "a.txt"], &results)
+        );
+    }
+}
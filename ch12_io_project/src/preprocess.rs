@@ -0,0 +1,144 @@
+// Some files need converting to text before there's anything to search, PDFs
+// being the usual example.  '--pre COMMAND' runs each file through COMMAND
+// and searches whatever it prints instead of the file itself.
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+pub struct Preprocessor {
+    // The program to run, and any arguments for it, separated by spaces
+    pub command: String,
+    // Only files whose names match one of these are preprocessed.  With none,
+    // every file is
+    pub globs: Vec<String>,
+}
+
+// What went wrong running the command for one file
+#[derive(Debug)]
+pub struct PreprocessError(pub String);
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "preprocessor failed: {}", self.0)
+    }
+}
+
+impl Error for PreprocessError {}
+
+impl Preprocessor {
+    // Globs are matched against the last part of the name only, so '*.pdf'
+    // matches 'docs/manual.pdf' and 'build.zip:docs/manual.pdf' alike
+    pub fn applies_to(&self, name: &str) -> bool {
+        let file_name = name.rsplit(['/', '\\', ':']).next().unwrap_or(name);
+        self.globs.is_empty() || self.globs.iter().any(|glob| glob_matches(glob, file_name))
+    }
+
+    // The command gets the file's name as its last argument and the file's
+    // contents on stdin, so it can use whichever suits it.  Files inside
+    // archives don't exist on disk, so for those only stdin is any use.
+    pub fn run(&self, name: &str, bytes: &[u8]) -> Result<Vec<u8>, PreprocessError> {
+        let mut words = self.command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| PreprocessError("no command given".to_string()))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .arg(name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| PreprocessError(format!("couldn't run '{}': {}", program, e)))?;
+
+        // If we wrote all of stdin before reading any stdout, a command that
+        // prints a lot could fill the pipe and wait on us while we wait on it.
+        // Writing from another thread means both sides keep moving.  Commands
+        // that only look at the file name never read stdin, so an error
+        // writing to it is ignored.
+        let mut stdin = child.stdin.take().unwrap();
+        let input = bytes.to_vec();
+        let writer = thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| PreprocessError(e.to_string()))?;
+        let _ = writer.join();
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            let errors = String::from_utf8_lossy(&output.stderr);
+            Err(PreprocessError(format!(
+                "{}: {}",
+                output.status,
+                errors.trim()
+            )))
+        }
+    }
+}
+
+// A small glob matcher: '*' matches any run of characters (including none) and
+// '?' matches exactly one.  Everything else has to match as-is.
+pub fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Where to go back to if what follows the last '*' stops matching
+    let mut star = None;
+    let (mut g, mut n) = (0, 0);
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, n));
+                g += 1;
+            }
+            Some('?') => {
+                g += 1;
+                n += 1;
+            }
+            Some(&c) if c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            // Let the last '*' swallow one more character and try again
+            _ => match star {
+                Some((star_g, star_n)) => {
+                    g = star_g + 1;
+                    n = star_n + 1;
+                    star = Some((star_g, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_matches("*.pdf", "manual.pdf"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("report-??.txt", "report-01.txt"));
+        assert!(glob_matches("*a*b", "xxaxxbxb"));
+        assert!(!glob_matches("*.pdf", "manual.pdf.txt"));
+        assert!(!glob_matches("report-?.txt", "report-01.txt"));
+    }
+
+    #[test]
+    fn applies_to_file_names() {
+        let pre = Preprocessor {
+            command: "pdftotext".to_string(),
+            globs: vec!["*.pdf".to_string()],
+        };
+        assert!(pre.applies_to("docs/manual.pdf"));
+        assert!(pre.applies_to("build.zip:manual.pdf"));
+        assert!(!pre.applies_to("manual.pdf/notes.txt"));
+    }
+}
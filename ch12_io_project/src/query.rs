@@ -0,0 +1,322 @@
+// Queries made of several terms joined with AND, OR and NOT, turned on with
+// '--bool'.  For example 'error AND NOT (timeout OR retry)' matches lines
+// that have 'error' in them but neither 'timeout' nor 'retry'.  The query is
+// parsed once into a tree, then the tree is checked against each line.
+//
+// The operators have to be written in capitals, so 'and' on its own is just a
+// term.  A term with spaces in it, or one that would otherwise be an operator,
+// can be put in double quotes: '"connection refused" OR "NOT"'.
+use std::ops::Range;
+
+use crate::{contains_ignoring_case, match_ranges};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Term(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Term(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Term(term) => format!("'{}'", term),
+            Token::And => "AND".to_string(),
+            Token::Or => "OR".to_string(),
+            Token::Not => "NOT".to_string(),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+        }
+    }
+}
+
+// Each token along with the column it starts at, counting characters from 1,
+// so that errors can point at the problem
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().enumerate().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let column = index + 1;
+        match c {
+            _ if c.is_whitespace() => {}
+            '(' => tokens.push((Token::Open, column)),
+            ')' => tokens.push((Token::Close, column)),
+            '"' => {
+                // Inside quotes, \" is a quote and \\ is a backslash
+                let mut term = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => term.push(escaped),
+                            None => return Err(format!("unclosed quote at column {}", column)),
+                        },
+                        Some((_, c)) => term.push(c),
+                        None => return Err(format!("unclosed quote at column {}", column)),
+                    }
+                }
+                if term.is_empty() {
+                    return Err(format!("empty quotes at column {}", column));
+                }
+                tokens.push((Token::Term(term), column));
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Term(word),
+                };
+                tokens.push((token, column));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// A recursive descent parser, with one function for each level of the
+// grammar.  NOT binds tightest, then AND, then OR, so 'a OR b AND NOT c' is
+// 'a OR (b AND (NOT c))'.
+//
+//     or      = and { "OR" and }
+//     and     = not { "AND" not }
+//     not     = "NOT" not | primary
+//     primary = term | "(" or ")"
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    // The column just past the end of the query, for errors about running out
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |&(_, column)| column)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.position += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let column = self.column();
+        match self.tokens.get(self.position).cloned() {
+            Some((Token::Term(term), _)) => {
+                self.position += 1;
+                Ok(Expr::Term(term))
+            }
+            Some((Token::Open, _)) => {
+                self.position += 1;
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(format!("missing ')' for the '(' at column {}", column));
+                }
+                self.position += 1;
+                Ok(expr)
+            }
+            Some((token, _)) => Err(format!(
+                "expected a term but found {} at column {}",
+                token.describe(),
+                column
+            )),
+            None => Err(format!(
+                "expected a term at column {}, but the query ended",
+                column
+            )),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(query: &str) -> Result<Expr, String> {
+        let tokens = tokenize(query)?;
+        if tokens.is_empty() {
+            return Err("the query is empty".to_string());
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end: query.chars().count() + 1,
+        };
+        let expr = parser.or()?;
+        // Anything left over means two terms with nothing joining them, or a
+        // ')' with no '(' to go with it
+        if let Some((token, column)) = parser.tokens.get(parser.position) {
+            let hint = match token {
+                Token::Close => "",
+                _ => " (use AND or OR between terms, or quotes around a phrase)",
+            };
+            return Err(format!(
+                "unexpected {} at column {}{}",
+                token.describe(),
+                column,
+                hint
+            ));
+        }
+        Ok(expr)
+    }
+
+    pub fn matches(&self, line: &str, case_sensitive: bool) -> bool {
+        match self {
+            Expr::Term(term) if case_sensitive => line.contains(term.as_str()),
+            Expr::Term(term) => contains_ignoring_case(line, term),
+            Expr::Not(inner) => !inner.matches(line, case_sensitive),
+            Expr::And(left, right) => {
+                left.matches(line, case_sensitive) && right.matches(line, case_sensitive)
+            }
+            Expr::Or(left, right) => {
+                left.matches(line, case_sensitive) || right.matches(line, case_sensitive)
+            }
+        }
+    }
+
+    // The terms that a matching line could contain, which is every term not
+    // under a NOT.  These are the ones worth highlighting.
+    fn positive_terms<'a>(&'a self, negated: bool, terms: &mut Vec<&'a str>) {
+        match self {
+            Expr::Term(term) if !negated => terms.push(term),
+            Expr::Term(_) => {}
+            Expr::Not(inner) => inner.positive_terms(!negated, terms),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.positive_terms(negated, terms);
+                right.positive_terms(negated, terms);
+            }
+        }
+    }
+
+    // Where the positive terms appear in line, in order.  Terms can overlap
+    // (say 'tea' and 'team'), in which case their ranges are joined.
+    pub fn ranges(&self, line: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+        let mut terms = Vec::new();
+        self.positive_terms(false, &mut terms);
+        let mut ranges: Vec<Range<usize>> = terms
+            .iter()
+            .flat_map(|term| match_ranges(term, line, case_sensitive))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(text: &str) -> Box<Expr> {
+        Box::new(Expr::Term(text.to_string()))
+    }
+
+    #[test]
+    fn parses_with_precedence() {
+        assert_eq!(
+            Ok(Expr::Or(
+                term("a"),
+                Box::new(Expr::And(term("b"), Box::new(Expr::Not(term("c")))))
+            )),
+            Expr::parse("a OR b AND NOT c")
+        );
+        assert_eq!(
+            Ok(Expr::And(
+                Box::new(Expr::Or(term("a"), term("b"))),
+                term("two words")
+            )),
+            Expr::parse("(a OR b) AND \"two words\"")
+        );
+        assert_eq!(Ok(*term("and")), Expr::parse("and"));
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!(
+            Err("missing ')' for the '(' at column 7".to_string()),
+            Expr::parse("a AND (b OR c")
+        );
+        assert_eq!(
+            Err("expected a term at column 10, but the query ended".to_string()),
+            Expr::parse("error AND")
+        );
+        assert_eq!(
+            Err("expected a term but found OR at column 7".to_string()),
+            Expr::parse("a AND OR b")
+        );
+        assert_eq!(
+            Err("unexpected ')' at column 3".to_string()),
+            Expr::parse("a ) b")
+        );
+        assert!(Expr::parse("error timeout")
+            .unwrap_err()
+            .starts_with("unexpected 'timeout' at column 7"));
+        assert!(Expr::parse("\"open").is_err());
+        assert!(Expr::parse("  ").is_err());
+    }
+
+    #[test]
+    fn evaluates_per_line() {
+        let expr = Expr::parse("error AND NOT timeout").unwrap();
+        assert!(expr.matches("error: disk full", true));
+        assert!(!expr.matches("error: timeout", true));
+        assert!(!expr.matches("all good", true));
+        assert!(expr.matches("ERROR: disk full", false));
+        assert!(!expr.matches("ERROR: TIMEOUT", false));
+
+        let expr = Expr::parse("tea OR team OR NOT cup").unwrap();
+        assert_eq!(vec![4..8], expr.ranges("the team", true));
+        assert_eq!(vec![0..3], expr.ranges("TEA", false));
+    }
+}
@@ -0,0 +1,238 @@
+// Searching only part of a file, given as a range of lines ('--lines 100:200')
+// or of bytes ('--bytes 0:4096').  The file is read a piece at a time and we
+// stop as soon as we're past the end of the range, so a search of the first
+// few lines of a huge log doesn't have to read the whole thing.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    // Line numbers count from 1, and both ends are included, so 100:200 is
+    // 101 lines
+    Lines { first: usize, last: Option<usize> },
+    // Byte offsets count from 0, and the end isn't included, the same as
+    // slicing.  0:4096 is the first 4096 bytes.  A range starting partway
+    // through a line is moved back to the start of that line, so the line is
+    // searched and printed whole.
+    Bytes { start: usize, end: Option<usize> },
+}
+
+// How much of the file came before the part we read.  Adding these to what
+// the search finds gives line numbers and offsets for the whole file.
+#[derive(Debug, Default, PartialEq)]
+pub struct Skipped {
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+impl Region {
+    // Either end can be left off: '100:' runs to the end of the file and ':50'
+    // starts at the beginning
+    pub fn parse_lines(value: &str) -> Result<Region, &'static str> {
+        let (first, last) = parse_range(value).ok_or("--lines must look like 100:200")?;
+        let first = first.unwrap_or(1);
+        if first == 0 {
+            return Err("--lines counts from 1");
+        }
+        if last.is_some_and(|last| last < first) {
+            return Err("--lines range ends before it starts");
+        }
+        Ok(Region::Lines { first, last })
+    }
+
+    pub fn parse_bytes(value: &str) -> Result<Region, &'static str> {
+        let (start, end) = parse_range(value).ok_or("--bytes must look like 0:4096")?;
+        let start = start.unwrap_or(0);
+        if end.is_some_and(|end| end < start) {
+            return Err("--bytes range ends before it starts");
+        }
+        Ok(Region::Bytes { start, end })
+    }
+
+    pub fn read(&self, path: &str) -> io::Result<(Vec<u8>, Skipped)> {
+        self.read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read_from(&self, mut reader: impl BufRead) -> io::Result<(Vec<u8>, Skipped)> {
+        // Finding lines means looking for '\n' bytes, which only works when
+        // every character is made of whole bytes.  In UTF-16 a '\n' byte can
+        // just as well be half of some other character.
+        let head = reader.fill_buf()?;
+        if head.starts_with(&[0xFF, 0xFE]) || head.starts_with(&[0xFE, 0xFF]) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--lines and --bytes don't work on UTF-16 files",
+            ));
+        }
+
+        match *self {
+            Region::Lines { first, last } => read_lines(reader, first, last),
+            Region::Bytes { start, end } => read_bytes(reader, start, end),
+        }
+    }
+}
+
+fn parse_range(value: &str) -> Option<(Option<usize>, Option<usize>)> {
+    let (start, end) = value.split_once(':')?;
+    let number = |text: &str| -> Option<Option<usize>> {
+        if text.is_empty() {
+            Some(None)
+        } else {
+            text.parse().ok().map(Some)
+        }
+    };
+    Some((number(start)?, number(end)?))
+}
+
+fn read_lines(
+    mut reader: impl BufRead,
+    first: usize,
+    last: Option<usize>,
+) -> io::Result<(Vec<u8>, Skipped)> {
+    let mut skipped = Skipped::default();
+    let mut contents = Vec::new();
+    let mut line = Vec::new();
+    let mut line_number = 0;
+
+    while last.is_none_or(|last| line_number < last) {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        line_number += 1;
+        if line_number < first {
+            skipped.lines += 1;
+            skipped.bytes += read;
+        } else {
+            contents.extend_from_slice(&line);
+        }
+    }
+    Ok((contents, skipped))
+}
+
+fn read_bytes(
+    mut reader: impl BufRead,
+    start: usize,
+    end: Option<usize>,
+) -> io::Result<(Vec<u8>, Skipped)> {
+    // The bytes before the range still have to be read to know how many lines
+    // they hold, but they're only counted.  All that's kept is the line the
+    // range starts in, as far as it's got.
+    let mut skipped = Skipped::default();
+    let mut line_start = Vec::new();
+    while skipped.bytes < start {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        let take = buffer.len().min(start - skipped.bytes);
+        let piece = &buffer[..take];
+        skipped.lines += piece.iter().filter(|&&b| b == b'\n').count();
+        match piece.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => {
+                line_start.clear();
+                line_start.extend_from_slice(&piece[newline + 1..]);
+            }
+            None => line_start.extend_from_slice(piece),
+        }
+        skipped.bytes += take;
+        reader.consume(take);
+    }
+    // The range is searched from the start of the line it begins in
+    skipped.bytes -= line_start.len();
+    let mut contents = line_start;
+    match end {
+        Some(end) => reader
+            .by_ref()
+            .take((end - start) as u64)
+            .read_to_end(&mut contents)?,
+        None => reader.read_to_end(&mut contents)?,
+    };
+    // Whether the range stopped before the end of the file
+    let cut_short = !reader.fill_buf()?.is_empty();
+
+    // The range can easily end partway through a UTF-8 character (it can't
+    // start in one, since it starts at the start of a line).  If dropping that
+    // piece leaves valid UTF-8, that's what we search.  Otherwise the file is
+    // in some other encoding and we leave it alone.  Only an end the range
+    // actually cut is looked at, so a file that just happens to end in a byte
+    // like Latin-1's 'é' keeps it.
+    if cut_short {
+        if let Err(e) = std::str::from_utf8(&contents) {
+            // Nothing is dropped unless what's left really is UTF-8
+            if e.error_len().is_none() {
+                contents.truncate(e.valid_up_to());
+            }
+        }
+    }
+    Ok((contents, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"one\ntwo\nthree\nfour\n";
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(
+            Ok(Region::Lines {
+                first: 100,
+                last: Some(200)
+            }),
+            Region::parse_lines("100:200")
+        );
+        assert_eq!(
+            Ok(Region::Bytes {
+                start: 0,
+                end: None
+            }),
+            Region::parse_bytes(":")
+        );
+        assert!(Region::parse_lines("0:5").is_err());
+        assert!(Region::parse_lines("5:4").is_err());
+        assert!(Region::parse_bytes("12").is_err());
+    }
+
+    #[test]
+    fn reads_line_ranges() {
+        let region = Region::parse_lines("2:3").unwrap();
+        let (contents, skipped) = region.read_from(TEXT).unwrap();
+        assert_eq!(b"two\nthree\n".to_vec(), contents);
+        assert_eq!(Skipped { lines: 1, bytes: 4 }, skipped);
+
+        let region = Region::parse_lines("4:10").unwrap();
+        assert_eq!(b"four\n".to_vec(), region.read_from(TEXT).unwrap().0);
+    }
+
+    #[test]
+    fn reads_byte_ranges() {
+        let region = Region::parse_bytes("9:17").unwrap();
+        let (contents, skipped) = region.read_from(TEXT).unwrap();
+        // 9 is partway through "three", so that line is searched whole
+        assert_eq!(b"three\nfou".to_vec(), contents);
+        assert_eq!(Skipped { lines: 2, bytes: 8 }, skipped);
+        let region = Region::parse_bytes("8:").unwrap();
+        let (contents, skipped) = region.read_from(TEXT).unwrap();
+        assert_eq!(b"three\nfour\n".to_vec(), contents);
+        assert_eq!(Skipped { lines: 2, bytes: 8 }, skipped);
+
+        // 'é' is two bytes, and the range cuts the second one in half
+        let region = Region::parse_bytes("1:3").unwrap();
+        let (contents, skipped) = region.read_from("éé".as_bytes()).unwrap();
+        assert_eq!("é".as_bytes().to_vec(), contents);
+        assert_eq!(0, skipped.bytes);
+
+        // Latin-1 isn't UTF-8 with pieces missing, so none of it is dropped
+        let latin1 = b"caf\xe9\nna\xefve caf\xe9";
+        let region = Region::parse_bytes("7:").unwrap();
+        let (contents, skipped) = region.read_from(&latin1[..]).unwrap();
+        assert_eq!(latin1[5..].to_vec(), contents);
+        assert_eq!(Skipped { lines: 1, bytes: 5 }, skipped);
+        let region = Region::parse_bytes("0:8").unwrap();
+        let (contents, _) = region.read_from(&latin1[..]).unwrap();
+        assert_eq!(latin1[..8].to_vec(), contents);
+    }
+}
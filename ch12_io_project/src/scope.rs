@@ -0,0 +1,285 @@
+// Searching only part of a Rust source file: its code, its comments, or its
+// string literals.  To know which part a match landed in we run a small lexer
+// over the file that finds every comment and literal.  It doesn't understand
+// the rest of Rust at all, since everything that isn't a comment or literal
+// counts as code.
+use std::ops::Range;
+
+use crate::{lines_around, match_ranges, Config, Match};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Code,
+    // Line, block and doc comments
+    Comments,
+    // String, raw string, byte string and char literals
+    Strings,
+}
+
+impl Scope {
+    // '--scope' takes one name, or several separated by commas
+    pub fn parse_list(value: &str) -> Result<Vec<Scope>, &'static str> {
+        value
+            .split(',')
+            .map(|name| match name {
+                "code" => Ok(Scope::Code),
+                "comments" => Ok(Scope::Comments),
+                "strings" => Ok(Scope::Strings),
+                _ => Err("--scope must be code, comments or strings"),
+            })
+            .collect()
+    }
+}
+
+// Every comment and literal in source, in order, as byte ranges along with
+// which of the two they are
+pub fn tokens(source: &str) -> Vec<(Range<usize>, Scope)> {
+    // All of the characters the lexer cares about are ASCII, so it can step
+    // through bytes.  It never stops in the middle of a multi-byte character,
+    // since none of the bytes in those are ASCII.
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                tokens.push((start..i, Scope::Comments));
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = block_comment_end(bytes, i);
+                tokens.push((start..i, Scope::Comments));
+            }
+            b'"' => {
+                i = string_end(bytes, i + 1);
+                tokens.push((start..i, Scope::Strings));
+            }
+            b'\'' => match char_end(source, i + 1) {
+                Some(end) => {
+                    i = end;
+                    tokens.push((start..i, Scope::Strings));
+                }
+                // A lifetime or loop label, like 'a or 'outer
+                None => i += 1,
+            },
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                // Literals can have a prefix: b"bytes", c"c string", b'x' and
+                // raw strings like r"..." or br#"..."#.  Anything else is just
+                // an identifier or a keyword.
+                let end = match (&source[start..i], bytes.get(i)) {
+                    ("r" | "br" | "cr", Some(b'"' | b'#')) => raw_string_end(bytes, i),
+                    ("b" | "c", Some(b'"')) => Some(string_end(bytes, i + 1)),
+                    ("b", Some(b'\'')) => char_end(source, i + 1),
+                    _ => None,
+                };
+                if let Some(end) = end {
+                    i = end;
+                    tokens.push((start..i, Scope::Strings));
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    tokens
+}
+
+// Block comments can nest in Rust, so '/* a /* b */ c */' is all one comment
+fn block_comment_end(bytes: &[u8], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'/', Some(b'*')) => {
+                depth += 1;
+                i += 2;
+            }
+            (b'*', Some(b'/')) => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+// 'i' is just past the opening quote.  Returns the position just past the
+// closing one, skipping over escaped quotes like \"
+fn string_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+// 'i' is just past the 'r'.  A raw string has no escapes, and ends at a quote
+// followed by as many #s as it started with, so r#"say "hi""# is one string.
+// Returns None for raw identifiers such as r#match.
+fn raw_string_end(bytes: &[u8], mut i: usize) -> Option<usize> {
+    let hashes = bytes[i..].iter().take_while(|&&b| b == b'#').count();
+    i += hashes;
+    if bytes.get(i) != Some(&b'"') {
+        return None;
+    }
+    i += 1;
+    while i < bytes.len() {
+        if bytes[i] == b'"'
+            && bytes[i + 1..]
+                .iter()
+                .take(hashes)
+                .filter(|&&b| b == b'#')
+                .count()
+                == hashes
+        {
+            return Some(i + 1 + hashes);
+        }
+        i += 1;
+    }
+    Some(bytes.len())
+}
+
+// 'i' is just past a single quote.  Char literals and lifetimes both start
+// with one, but a char literal is a single (possibly escaped) character
+// followed by a closing quote, which a lifetime never is.
+fn char_end(source: &str, i: usize) -> Option<usize> {
+    let rest = &source[i..];
+    let mut chars = rest.char_indices();
+    match chars.next()? {
+        (_, '\\') => {
+            // Skip the backslash and the character after it, which might be
+            // an escaped quote
+            let (escaped, c) = chars.next()?;
+            let after = escaped + c.len_utf8();
+            let close = rest[after..].find('\'')?;
+            // Escapes are short (\n, \', \u{1F980}), so a quote a long way
+            // off isn't the end of this one
+            if close <= 8 {
+                Some(i + after + close + 1)
+            } else {
+                None
+            }
+        }
+        (_, '\n') | (_, '\'') => None,
+        (_, _) => match chars.next()? {
+            (close, '\'') => Some(i + close + 1),
+            _ => None,
+        },
+    }
+}
+
+// Which scope a match falls in.  A match only counts as a comment or string
+// match if it's entirely inside one, and only counts as code if it doesn't
+// touch any comment or string at all
+fn in_scopes(range: &Range<usize>, tokens: &[(Range<usize>, Scope)], scopes: &[Scope]) -> bool {
+    // The tokens are in order and don't overlap, so we can jump straight to
+    // the first one that ends after the match starts
+    let first = tokens.partition_point(|(token, _)| token.end <= range.start);
+    let overlapping = tokens[first..]
+        .iter()
+        .take_while(|(token, _)| token.start < range.end.max(range.start + 1));
+    let mut found_in = None;
+    for (token, scope) in overlapping {
+        if token.start <= range.start && range.end <= token.end && found_in.is_none() {
+            found_in = Some(*scope);
+        } else {
+            return false;
+        }
+    }
+    scopes.contains(&found_in.unwrap_or(Scope::Code))
+}
+
+// Like search_lines, but a line only matches if the query appears in it
+// within one of the chosen scopes
+pub fn search_scoped<'a>(config: &Config, scopes: &[Scope], contents: &'a str) -> Vec<Match<'a>> {
+    let tokens = tokens(contents);
+    let ranges = match_ranges(&config.query, contents, config.case_sensitive)
+        .into_iter()
+        // Without --multiline, the query can't match across lines
+        .filter(|range| config.multiline || !contents[range.clone()].contains('\n'))
+        .filter(|range| in_scopes(range, &tokens, scopes))
+        .collect();
+    lines_around(contents, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r####"
+/// Doc comment about a lifetime
+fn longest<'a>(x: &'a str) -> &'a str {
+    let lifetime = 'x';
+    /* a /* nested */ lifetime */
+    println!("{}", "the lifetime");
+    let raw = r#"a "quoted" lifetime"#;
+    let quote = '\'';
+    lifetime
+}
+"####;
+
+    fn scoped(query: &str, scopes: &[Scope]) -> Vec<usize> {
+        let args: Vec<String> = ["io_project", query, "main.rs"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut config = Config::new(&args).unwrap();
+        config.case_sensitive = true;
+        search_scoped(&config, scopes, SOURCE)
+            .iter()
+            .map(|found| found.line_number)
+            .collect()
+    }
+
+    #[test]
+    fn finds_comments_and_strings() {
+        let found: Vec<(&str, Scope)> = tokens(SOURCE)
+            .into_iter()
+            .map(|(range, scope)| (&SOURCE[range], scope))
+            .collect();
+        assert_eq!(
+            vec![
+                ("/// Doc comment about a lifetime", Scope::Comments),
+                ("'x'", Scope::Strings),
+                ("/* a /* nested */ lifetime */", Scope::Comments),
+                ("\"{}\"", Scope::Strings),
+                ("\"the lifetime\"", Scope::Strings),
+                ("r#\"a \"quoted\" lifetime\"#", Scope::Strings),
+                ("'\\''", Scope::Strings),
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn matches_only_in_chosen_scopes() {
+        assert_eq!(vec![4, 9], scoped("lifetime", &[Scope::Code]));
+        assert_eq!(vec![2, 5], scoped("lifetime", &[Scope::Comments]));
+        assert_eq!(vec![6, 7], scoped("lifetime", &[Scope::Strings]));
+        assert_eq!(
+            vec![2, 5, 6, 7],
+            scoped("lifetime", &[Scope::Comments, Scope::Strings])
+        );
+        // The 'a lifetimes are code, not the start of char literals
+        assert_eq!(vec![3], scoped("'a", &[Scope::Code]));
+    }
+
+    #[test]
+    fn parses_scope_lists() {
+        assert_eq!(
+            Ok(vec![Scope::Comments, Scope::Strings]),
+            Scope::parse_list("comments,strings")
+        );
+        assert!(Scope::parse_list("identifiers").is_err());
+    }
+}
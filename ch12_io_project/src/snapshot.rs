@@ -0,0 +1,326 @@
+// Saving the results of a search to a file ('--save-snapshot') and later
+// comparing a new search against them ('--diff-snapshot'), to see which
+// matches have appeared, disappeared or moved since.
+//
+// A match is known by its file and the text of the line, not by its line
+// number, so a line that shifts down because something was added above it
+// shows up as moved rather than as one match disappearing and another
+// appearing.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::output::FileMatches;
+
+// The first line of every snapshot, so we can tell if we're handed something
+// else, or a snapshot from a future version with a different layout
+const HEADER: &str = "io_project snapshot 1";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: String,
+    pub line_number: usize,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Appeared(Entry),
+    Disappeared(Entry),
+    // 'entry' has the new line number
+    Moved { entry: Entry, from: usize },
+}
+
+// How many of each kind of change print_diff printed, for a summary
+#[derive(Debug, Default, PartialEq)]
+pub struct Counts {
+    pub appeared: usize,
+    pub disappeared: usize,
+    pub moved: usize,
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} appeared, {} disappeared, {} moved",
+            self.appeared, self.disappeared, self.moved
+        )
+    }
+}
+
+pub fn entries(results: &[FileMatches]) -> Vec<Entry> {
+    results
+        .iter()
+        .flat_map(|file| {
+            file.lines.iter().map(move |line| Entry {
+                path: file.name.clone(),
+                line_number: line.line_number,
+                text: line.text.clone(),
+            })
+        })
+        .collect()
+}
+
+// One match per line, as 'path<TAB>line number<TAB>text'.  Tabs, newlines
+// and backslashes in the path or text are escaped so they can't break up the
+// line, which keeps the file easy to read and to diff by hand.
+pub fn save(path: &str, entries: &[Entry]) -> io::Result<()> {
+    let mut out = Vec::new();
+    writeln!(out, "{}", HEADER)?;
+    for entry in entries {
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            escape(&entry.path),
+            entry.line_number,
+            escape(&entry.text)
+        )?;
+    }
+    fs::write(path, out)
+}
+
+pub fn load(path: &str) -> io::Result<Vec<Entry>> {
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(invalid_data(format!(
+            "{} isn't an io_project snapshot",
+            path
+        )));
+    }
+
+    let mut entries = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let mut parts = line.splitn(3, '\t');
+        let entry = match (parts.next(), parts.next(), parts.next()) {
+            (Some(path), Some(line_number), Some(text)) => {
+                line_number.parse().ok().map(|n| Entry {
+                    path: unescape(path),
+                    line_number: n,
+                    text: unescape(text),
+                })
+            }
+            _ => None,
+        };
+        // The header is line 1, so the entries start on line 2
+        let entry = entry
+            .ok_or_else(|| invalid_data(format!("{}: line {} is malformed", path, index + 2)))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// The same line can match more than once in a file (a blank line, say, or a
+// repeated log message), so each path and text maps to all of the line numbers
+// it was found on.  Numbers found in both runs haven't changed.  The rest are
+// paired up in order as moves, and whatever is left over on one side or the
+// other appeared or disappeared.
+pub fn diff(old: Vec<Entry>, new: Vec<Entry>) -> Vec<Change> {
+    let mut by_key: BTreeMap<(String, String), (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+    for entry in old {
+        by_key
+            .entry((entry.path, entry.text))
+            .or_default()
+            .0
+            .push(entry.line_number);
+    }
+    for entry in new {
+        by_key
+            .entry((entry.path, entry.text))
+            .or_default()
+            .1
+            .push(entry.line_number);
+    }
+
+    let mut changes = Vec::new();
+    for ((path, text), (old_lines, new_lines)) in by_key {
+        let gone: Vec<usize> = old_lines
+            .iter()
+            .filter(|n| !new_lines.contains(n))
+            .copied()
+            .collect();
+        let came: Vec<usize> = new_lines
+            .iter()
+            .filter(|n| !old_lines.contains(n))
+            .copied()
+            .collect();
+        let entry = |line_number| Entry {
+            path: path.clone(),
+            line_number,
+            text: text.clone(),
+        };
+
+        for (&from, &to) in gone.iter().zip(&came) {
+            changes.push(Change::Moved {
+                entry: entry(to),
+                from,
+            });
+        }
+        for &line_number in gone.iter().skip(came.len()) {
+            changes.push(Change::Disappeared(entry(line_number)));
+        }
+        for &line_number in came.iter().skip(gone.len()) {
+            changes.push(Change::Appeared(entry(line_number)));
+        }
+    }
+
+    // Grouped by file, then in line order, the way the results themselves
+    // are printed
+    changes.sort_by(|a, b| {
+        let (a, b) = (a.entry(), b.entry());
+        (&a.path, a.line_number).cmp(&(&b.path, b.line_number))
+    });
+    changes
+}
+
+impl Change {
+    pub fn entry(&self) -> &Entry {
+        match self {
+            Change::Appeared(entry) | Change::Disappeared(entry) | Change::Moved { entry, .. } => {
+                entry
+            }
+        }
+    }
+}
+
+// '+' for matches that appeared, '-' for ones that disappeared and '~' for
+// ones that moved.  How many there were of each is returned, for the caller
+// to report wherever suits it.
+pub fn print_diff(changes: &[Change], out: &mut impl Write) -> io::Result<Counts> {
+    let mut counts = Counts::default();
+    for change in changes {
+        match change {
+            Change::Appeared(entry) => {
+                counts.appeared += 1;
+                writeln!(out, "+ {}:{}:{}", entry.path, entry.line_number, entry.text)?;
+            }
+            Change::Disappeared(entry) => {
+                counts.disappeared += 1;
+                writeln!(out, "- {}:{}:{}", entry.path, entry.line_number, entry.text)?;
+            }
+            Change::Moved { entry, from } => {
+                counts.moved += 1;
+                writeln!(
+                    out,
+                    "~ {}:{}->{}:{}",
+                    entry.path, from, entry.line_number, entry.text
+                )?;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, line_number: usize, text: &str) -> Entry {
+        Entry {
+            path: path.to_string(),
+            line_number,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let dir = std::env::temp_dir().join(format!("io_project_snapshot_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("results.snapshot");
+        let path = path.to_str().unwrap();
+
+        let entries = vec![
+            entry("odd\tname.txt", 3, "a\\b"),
+            entry("poem.txt", 7, "two\nlines\r"),
+        ];
+        save(path, &entries).unwrap();
+        assert_eq!(entries, load(path).unwrap());
+
+        fs::write(path, "io_project snapshot 1\npoem.txt\tseven\tfrog\n").unwrap();
+        assert_eq!(
+            format!("{}: line 2 is malformed", path),
+            load(path).unwrap_err().to_string()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_changes() {
+        let old = vec![
+            entry("a.txt", 1, "frog"),
+            entry("a.txt", 4, "a frog"),
+            entry("a.txt", 9, "frog"),
+            entry("b.txt", 2, "old frog"),
+        ];
+        let new = vec![
+            entry("a.txt", 1, "frog"),
+            entry("a.txt", 6, "a frog"),
+            entry("a.txt", 12, "frog"),
+            entry("a.txt", 15, "frog"),
+            entry("c.txt", 1, "new frog"),
+        ];
+        assert_eq!(
+            vec![
+                Change::Moved {
+                    entry: entry("a.txt", 6, "a frog"),
+                    from: 4
+                },
+                Change::Moved {
+                    entry: entry("a.txt", 12, "frog"),
+                    from: 9
+                },
+                Change::Appeared(entry("a.txt", 15, "frog")),
+                Change::Disappeared(entry("b.txt", 2, "old frog")),
+                Change::Appeared(entry("c.txt", 1, "new frog")),
+            ],
+            diff(old.clone(), new.clone())
+        );
+
+        let mut out = Vec::new();
+        let counts = print_diff(&diff(old, new), &mut out).unwrap();
+        assert_eq!("2 appeared, 1 disappeared, 2 moved", counts.to_string());
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("~ a.txt:4->6:a frog\n"));
+    }
+}
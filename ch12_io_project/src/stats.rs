@@ -0,0 +1,128 @@
+// Counts of how much work a search did, printed with '--stats'.  run only
+// fills these in once per file after that file has been searched, so keeping
+// them costs nothing while the search itself is going.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::preprocess::PreprocessError;
+use crate::Match;
+
+#[derive(Default)]
+pub struct Stats {
+    pub files_searched: usize,
+    pub bytes_scanned: usize,
+    pub lines_scanned: usize,
+    // Every occurrence of the query, so a line with the query in it twice
+    // counts twice here but once in matched_lines
+    pub matches: usize,
+    pub matched_lines: usize,
+    // A BTreeMap keeps the reasons in the same order every time they print
+    pub skipped: BTreeMap<&'static str, usize>,
+    // How many of the skipped files were skipped because of an error, rather
+    // than on purpose
+    pub errors: usize,
+    pub elapsed: Duration,
+}
+
+// Returned instead of a real error when we choose not to search a file, such
+// as a binary file inside an archive.  These aren't reported as problems, only
+// counted under their reason.
+#[derive(Debug)]
+pub struct Skip(pub &'static str);
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "skipped ({})", self.0)
+    }
+}
+
+impl Error for Skip {}
+
+impl Stats {
+    pub fn add_file(&mut self, bytes: usize, text: &str, results: &[Match]) {
+        self.files_searched += 1;
+        self.bytes_scanned += bytes;
+        self.lines_scanned += text.lines().count();
+        for found in results {
+            self.matched_lines += found.end_line_number - found.line_number + 1;
+            // A line can match with nothing in it to highlight (one matched
+            // by a NOT in a --bool query, say), but it still matched once
+            self.matches += found.ranges.len().max(1);
+        }
+    }
+
+    pub fn add_skipped(&mut self, error: &(dyn Error + 'static)) {
+        if !error.is::<Skip>() {
+            self.errors += 1;
+        }
+        *self.skipped.entry(skip_reason(error)).or_insert(0) += 1;
+    }
+}
+
+// Sorts the errors that stop us searching a file into a few broad reasons.
+// Errors we didn't make ourselves are io::Errors, so we can look at their kind.
+fn skip_reason(error: &(dyn Error + 'static)) -> &'static str {
+    if let Some(Skip(reason)) = error.downcast_ref::<Skip>() {
+        return reason;
+    }
+    if error.is::<PreprocessError>() {
+        return "preprocessor failed";
+    }
+    match error.downcast_ref::<io::Error>().map(|e| e.kind()) {
+        Some(io::ErrorKind::NotFound) => "not found",
+        Some(io::ErrorKind::PermissionDenied) => "permission denied",
+        Some(io::ErrorKind::IsADirectory) => "directory",
+        Some(io::ErrorKind::InvalidData) => "not valid text",
+        Some(_) => "unreadable",
+        None => "error",
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "files searched: {}", self.files_searched)?;
+        writeln!(f, "bytes scanned: {}", self.bytes_scanned)?;
+        writeln!(f, "lines scanned: {}", self.lines_scanned)?;
+        writeln!(f, "matches: {}", self.matches)?;
+        writeln!(f, "matched lines: {}", self.matched_lines)?;
+        for (reason, count) in &self.skipped {
+            writeln!(f, "files skipped ({}): {}", reason, count)?;
+        }
+        writeln!(f, "elapsed: {:.3}s", self.elapsed.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_lines, Config};
+
+    #[test]
+    fn counts_matches_and_lines() {
+        let config = Config::new(&["io_project", "t", "poem.txt"].map(String::from)).unwrap();
+        let contents = "Rust:\ntrust, test,\nPick three.\n";
+        let results = search_lines(&config, contents);
+
+        let mut stats = Stats::default();
+        stats.add_file(contents.len(), contents, &results);
+        assert_eq!(1, stats.files_searched);
+        assert_eq!(3, stats.lines_scanned);
+        assert_eq!(3, stats.matched_lines);
+        assert_eq!(6, stats.matches);
+    }
+
+    #[test]
+    fn skip_reasons() {
+        let mut stats = Stats::default();
+        let missing = io::Error::new(io::ErrorKind::NotFound, "gone");
+        stats.add_skipped(&missing);
+        stats.add_skipped(&missing);
+        stats.add_skipped(&Skip("binary"));
+        assert_eq!(Some(&2), stats.skipped.get("not found"));
+        assert_eq!(Some(&1), stats.skipped.get("binary"));
+        assert_eq!(2, stats.errors);
+    }
+}
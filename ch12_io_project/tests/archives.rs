@@ -0,0 +1,102 @@
+// Reads archives built on the fly, and runs io_project over them, checking
+// that files inside them (and inside archives inside them) are unpacked,
+// searched and named properly.
+use std::fs;
+use std::io::Write;
+
+use io_project::archive::{members, Kind};
+
+mod common;
+
+use common::{run_io_project, tar_with, temp_dir, zip_with};
+
+fn gzipped(bytes: &[u8]) -> Vec<u8> {
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    gz.write_all(bytes).unwrap();
+    gz.finish().unwrap()
+}
+
+#[test]
+fn reads_tar_and_gzip_members() {
+    let bytes = tar_with(&[("docs/poem.txt", b"I'm nobody!"), ("empty", b"")]);
+    let found = members(Kind::Tar, &bytes, 100).unwrap();
+    assert_eq!(2, found.len());
+    assert_eq!("docs/poem.txt", found[0].path);
+    assert_eq!(Some(b"I'm nobody!".to_vec()), found[0].data);
+
+    let tarball = gzipped(&tar_with(&[("poem.txt", b"a frog")]));
+    assert_eq!(
+        Some(b"a frog".to_vec()),
+        members(Kind::TarGz, &tarball, 100).unwrap()[0].data
+    );
+}
+
+#[test]
+fn stops_reading_at_the_limit() {
+    // A megabyte of zeros squeezes down to about a kilobyte
+    let zeros = vec![0; 1024 * 1024];
+    let tarball = gzipped(&tar_with(&[("zeros", &zeros), ("small", b"frog")]));
+    assert!(tarball.len() < 4096);
+
+    let found = members(Kind::TarGz, &tarball, 1000).unwrap();
+    assert_eq!(None, found[0].data);
+    assert_eq!(Some(b"frog".to_vec()), found[1].data);
+}
+
+#[test]
+fn searches_nested_archives() {
+    let dir = temp_dir("nested_archives");
+    let poem = fs::read("poem.txt").unwrap();
+    let inner = tar_with(&[("docs/poem.txt", &poem), ("logo.png", b"\x89PNG\x00\x00")]);
+    fs::write(dir.join("outer.zip"), zip_with(&[("inner.tar", &inner)])).unwrap();
+
+    let (stdout, stderr, success) = run_io_project(&dir, &["-n", "--stats", "frog", "outer.zip"]);
+    assert!(success, "{}", stderr);
+    assert_eq!(
+        "outer.zip:inner.tar:docs/poem.txt:7:How public, like a frog\n",
+        stdout
+    );
+    assert!(stderr.contains("files skipped (binary): 1"), "{}", stderr);
+}
+
+#[test]
+fn stops_at_the_depth_limit() {
+    let dir = temp_dir("archive_depth");
+    let inner = tar_with(&[("poem.txt", b"like a frog")]);
+    fs::write(dir.join("outer.zip"), zip_with(&[("inner.tar", &inner)])).unwrap();
+
+    let (stdout, stderr, success) = run_io_project(
+        &dir,
+        &["--max-archive-depth", "1", "--stats", "frog", "outer.zip"],
+    );
+    assert!(success, "{}", stderr);
+    assert_eq!("", stdout);
+    assert!(
+        stderr.contains("files skipped (archive nested too deeply): 1"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn regions_leave_archives_out() {
+    let dir = temp_dir("archive_region");
+    fs::write(
+        dir.join("poems.tar"),
+        tar_with(&[("poem.txt", b"like a frog")]),
+    )
+    .unwrap();
+    fs::write(dir.join("poem.txt"), "like a frog\n").unwrap();
+
+    let (stdout, stderr, success) = run_io_project(
+        &dir,
+        &["--lines", "1:", "--stats", "frog", "poems.tar", "poem.txt"],
+    );
+    assert!(success, "{}", stderr);
+    assert_eq!("poem.txt:like a frog\n", stdout);
+    assert!(
+        stderr.contains("files skipped (--lines and --bytes don't work on archives): 1"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,130 @@
+// Shared by the integration tests and the benchmarks (which pull this file in
+// with a #[path] attribute).  Each test binary only uses some of it.
+#![allow(dead_code)]
+
+// A small xorshift random number generator.  It's nowhere near good enough for
+// anything that matters, but it's plenty for making up test input, and using a
+// fixed seed means every run sees the same input, so failures can be repeated.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck at zero forever, so make sure we never start there
+        Rng(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // A number from 0 up to (but not including) n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+// Pieces chosen to cause trouble: mixed case, Windows line endings, and
+// characters whose lowercase form is a different length or depends on where
+// they sit in a word ('Σ' becomes 'ς' at the end of a word but 'σ' otherwise)
+const PIECES: [&str; 20] = [
+    "a", "b", "r", "R", "u", "U", "s", "t", "T", " ", ",", "\n", "\r\n", "é", "É", "ß", "Σ", "σ",
+    "İ", "i",
+];
+
+pub fn random_text(rng: &mut Rng, max_pieces: usize) -> String {
+    let pieces = rng.below(max_pieces + 1);
+    (0..pieces).map(|_| *rng.pick(&PIECES)).collect()
+}
+
+// Usually a piece of the text itself, so that there's something to find, and
+// sometimes something made up that may not be in there at all
+pub fn random_query(rng: &mut Rng, text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() || rng.below(4) == 0 {
+        return random_text(rng, 3);
+    }
+    let start = rng.below(chars.len());
+    let len = 1 + rng.below(3.min(chars.len() - start));
+    chars[start..start + len].iter().collect()
+}
+
+const WORDS: [&str; 16] = [
+    "nobody", "somebody", "dreary", "public", "frog", "bog", "banish", "admiring", "Who", "are",
+    "you", "too", "tell", "your", "name", "livelong",
+];
+
+// Something that looks a bit more like a real file, for the benchmarks: lines
+// of words, each about line_len characters long, with the odd comma so that the
+// field search has something to split on
+pub fn corpus(rng: &mut Rng, lines: usize, line_len: usize) -> String {
+    let mut text = String::new();
+    for _ in 0..lines {
+        let start = text.len();
+        while text.len() - start < line_len {
+            let word = *rng.pick(&WORDS);
+            text.push_str(word);
+            text.push_str(if rng.below(8) == 0 { "," } else { " " });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// A fresh, empty directory for a test to put its files in.  Tests run in
+// parallel, so each one needs its own name
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("io_project_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// A tar file holding the given files, built in memory
+pub fn tar_with(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, *data).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+// The same as a zip file
+pub fn zip_with(files: &[(&str, &[u8])]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (path, data) in files {
+        writer
+            .start_file(*path, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+// Runs the io_project binary itself and hands back what it printed to stdout
+// and stderr, and whether it exited successfully
+pub fn run_io_project(dir: &std::path::Path, args: &[&str]) -> (String, String, bool) {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_io_project"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap();
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.success(),
+    )
+}
@@ -0,0 +1,80 @@
+// Runs io_project with a small shell script standing in for a real converter
+// like pdftotext.  The scripts need a Unix shell, so these only run there.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+mod common;
+
+use common::{run_io_project, temp_dir};
+
+fn write_script(dir: &Path, name: &str, body: &str) {
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn searches_preprocessor_output() {
+    let dir = temp_dir("preprocessor_output");
+    // Reads the file from stdin and shouts it
+    write_script(&dir, "upper.sh", "tr '[:lower:]' '[:upper:]'");
+    fs::write(dir.join("poem.txt"), "How public, like a frog\n").unwrap();
+    fs::write(dir.join("notes.md"), "a frog\n").unwrap();
+
+    let (stdout, stderr, success) = run_io_project(
+        &dir,
+        &[
+            "--pre",
+            "./upper.sh",
+            "--pre-glob",
+            "*.txt",
+            "FROG",
+            "poem.txt",
+            "notes.md",
+        ],
+    );
+    assert!(success, "{}", stderr);
+    // notes.md didn't match the glob, so it was searched as it is
+    assert_eq!("poem.txt:HOW PUBLIC, LIKE A FROG\n", stdout);
+}
+
+#[test]
+fn reports_preprocessor_failures_per_file() {
+    let dir = temp_dir("preprocessor_failure");
+    // Fails for one file and copies the others through, using the file name
+    // it's given rather than stdin
+    write_script(
+        &dir,
+        "picky.sh",
+        "if [ \"$1\" = bad.txt ]; then echo 'cannot convert' >&2; exit 3; fi; cat \"$1\"",
+    );
+    fs::write(dir.join("bad.txt"), "a frog\n").unwrap();
+    fs::write(dir.join("good.txt"), "a frog\n").unwrap();
+
+    let (stdout, stderr, success) = run_io_project(
+        &dir,
+        &[
+            "--pre",
+            "./picky.sh",
+            "--stats",
+            "frog",
+            "bad.txt",
+            "good.txt",
+        ],
+    );
+    assert!(!success);
+    assert_eq!("good.txt:a frog\n", stdout);
+    assert!(
+        stderr.contains("bad.txt: preprocessor failed: exit status: 3: cannot convert"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("files skipped (preprocessor failed): 1"),
+        "{}",
+        stderr
+    );
+}
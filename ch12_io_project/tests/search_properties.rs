@@ -0,0 +1,118 @@
+// Property tests: rather than checking one hand-written example, each test
+// makes up lots of random input and checks something that should be true for
+// all of it.  When one fails, the message includes the input that broke it.
+extern crate io_project;
+
+use io_project::{search, search_case_insensitive, search_lines, search_multiline, Config};
+
+mod common;
+
+use common::{random_query, random_text, Rng};
+
+const CASES: u64 = 2000;
+
+fn config(query: &str, case_sensitive: bool) -> Config {
+    let args: Vec<String> = ["io_project", query, "poem.txt"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let mut config = Config::new(&args).unwrap();
+    // Set directly so the tests don't depend on the CASE_INSENSITIVE variable
+    config.case_sensitive = case_sensitive;
+    config
+}
+
+#[test]
+fn case_insensitive_finds_everything_case_sensitive_does() {
+    let mut rng = Rng::new(1);
+    for _ in 0..CASES {
+        let contents = random_text(&mut rng, 40);
+        let query = random_query(&mut rng, &contents);
+
+        let sensitive = search(&query, &contents);
+        let insensitive = search_case_insensitive(&query, &contents);
+        for line in &sensitive {
+            assert!(
+                insensitive
+                    .iter()
+                    .any(|other| other.as_ptr() == line.as_ptr()),
+                "{:?} matched {:?} case-sensitively but not case-insensitively",
+                query,
+                line
+            );
+        }
+
+        let insensitive: Vec<usize> = search_lines(&config(&query, false), &contents)
+            .iter()
+            .map(|found| found.line_number)
+            .collect();
+        for found in search_lines(&config(&query, true), &contents) {
+            assert!(insensitive.contains(&found.line_number));
+        }
+    }
+}
+
+#[test]
+fn results_are_whole_lines_of_the_input() {
+    let mut rng = Rng::new(2);
+    for _ in 0..CASES {
+        let contents = random_text(&mut rng, 40);
+        let query = random_query(&mut rng, &contents);
+        let lines: Vec<&str> = contents.lines().collect();
+
+        for results in [
+            search(&query, &contents),
+            search_case_insensitive(&query, &contents),
+        ] {
+            for line in results {
+                assert!(
+                    lines.contains(&line),
+                    "{:?} is not a line of {:?}",
+                    line,
+                    contents
+                );
+            }
+        }
+        for case_sensitive in [true, false] {
+            for found in search_lines(&config(&query, case_sensitive), &contents) {
+                assert_eq!(lines[found.line_number - 1], found.line);
+                assert_eq!(
+                    found.line,
+                    &contents[found.offset..found.offset + found.line.len()]
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn multiline_agrees_with_line_search_for_single_line_queries() {
+    let mut rng = Rng::new(3);
+    for _ in 0..CASES {
+        let contents = random_text(&mut rng, 40);
+        let query = random_query(&mut rng, &contents);
+        if query.is_empty() || query.contains('\n') || query.contains('\r') {
+            continue;
+        }
+
+        // Ignoring case is where they're most likely to disagree, since
+        // 'İ' lowercases to an 'i' with a dot over it, so an 'i' on its own
+        // could seem to be found in the middle of it
+        for case_sensitive in [true, false] {
+            let config = config(&query, case_sensitive);
+            let by_line: Vec<usize> = search_lines(&config, &contents)
+                .iter()
+                .map(|found| found.line_number)
+                .collect();
+            let multiline: Vec<usize> = search_multiline(&config, &contents)
+                .iter()
+                .map(|found| found.line_number)
+                .collect();
+            assert_eq!(
+                by_line, multiline,
+                "query {:?} in {:?}, case sensitive: {}",
+                query, contents, case_sensitive
+            );
+        }
+    }
+}
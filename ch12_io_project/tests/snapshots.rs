@@ -0,0 +1,75 @@
+// Runs io_project with --save-snapshot and --diff-snapshot, checking that a
+// snapshot is only trusted when every file could be searched.
+use std::fs;
+
+mod common;
+
+use common::{run_io_project, temp_dir};
+
+#[test]
+fn only_saves_complete_snapshots() {
+    let dir = temp_dir("snapshot_save");
+    fs::write(dir.join("poem.txt"), "a frog\n").unwrap();
+
+    // missing.txt can't be read, so its matches would be left out
+    let (_, stderr, success) = run_io_project(
+        &dir,
+        &[
+            "--save-snapshot",
+            "frog.snapshot",
+            "frog",
+            "poem.txt",
+            "missing.txt",
+        ],
+    );
+    assert!(!success);
+    assert!(
+        stderr.contains("1 file could not be searched, so the snapshot wasn't saved"),
+        "{}",
+        stderr
+    );
+    assert!(!dir.join("frog.snapshot").exists());
+
+    let (_, stderr, success) = run_io_project(
+        &dir,
+        &["--save-snapshot", "frog.snapshot", "frog", "poem.txt"],
+    );
+    assert!(success, "{}", stderr);
+    assert!(dir.join("frog.snapshot").exists());
+}
+
+#[test]
+fn only_compares_complete_searches() {
+    let dir = temp_dir("snapshot_diff");
+    fs::write(dir.join("a.txt"), "a frog\n").unwrap();
+    fs::write(dir.join("b.txt"), "another frog\n").unwrap();
+    let (_, stderr, success) = run_io_project(
+        &dir,
+        &["--save-snapshot", "frog.snapshot", "frog", "a.txt", "b.txt"],
+    );
+    assert!(success, "{}", stderr);
+
+    // b.txt's frog hasn't gone anywhere, it just couldn't be read this time
+    fs::rename(dir.join("b.txt"), dir.join("b.bak")).unwrap();
+    let (stdout, stderr, success) = run_io_project(
+        &dir,
+        &["--diff-snapshot", "frog.snapshot", "frog", "a.txt", "b.txt"],
+    );
+    assert!(!success);
+    assert_eq!("", stdout);
+    assert!(
+        stderr.contains("so the snapshot wasn't compared"),
+        "{}",
+        stderr
+    );
+
+    fs::rename(dir.join("b.bak"), dir.join("b.txt")).unwrap();
+    fs::write(dir.join("a.txt"), "\na frog\n").unwrap();
+    let (stdout, stderr, success) = run_io_project(
+        &dir,
+        &["--diff-snapshot", "frog.snapshot", "frog", "a.txt", "b.txt"],
+    );
+    assert!(success, "{}", stderr);
+    assert_eq!("~ a.txt:1->2:a frog\n", stdout);
+    assert_eq!("0 appeared, 0 disappeared, 1 moved\n", stderr);
+}